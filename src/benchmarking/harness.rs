@@ -0,0 +1,332 @@
+use crate::adapter::KrakenAdapter;
+use crate::applier::ApplyPolicy;
+use crate::book::BookSet;
+use crate::checkpoint::BookCheckpoint;
+use crate::messages::KrakenMessage;
+use crate::recorder::{
+    CaptureError, CaptureHeader, CaptureReader, Record, RecordKind, Recorder, SubscriptionRecord,
+};
+use crate::stats::{QualityStats, SymbolLatencyStats, TimingStats};
+use chrono::{DateTime, Utc};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How fast a capture is fed back through the books. Pacing sleeps the
+/// replaying thread, so a paced replay must not run on an async runtime's
+/// worker thread, e.g. use `tokio::task::spawn_blocking`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReplaySpeed {
+    /// No waiting between records
+    #[default]
+    AsFastAsPossible,
+    /// Records are spaced as they were received
+    RealTime,
+    /// Real time sped up by the factor, so `Scaled(10.0)` replays ten
+    /// minutes of feed in one
+    Scaled(f64),
+}
+
+/// A [`ReplaySpeed::Scaled`] factor that isn't a finite positive number.
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+#[error("Replay speed factor must be finite and above zero, not {0}")]
+pub struct InvalidSpeed(pub f64);
+
+impl ReplaySpeed {
+    pub fn validate(&self) -> Result<(), InvalidSpeed> {
+        match *self {
+            ReplaySpeed::Scaled(factor) if !(factor.is_finite() && factor > 0.0) => {
+                Err(InvalidSpeed(factor))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// How long after the first record the one at `offset` into the capture
+    /// is due, or `None` to apply it straight away.
+    fn due(&self, offset: Duration) -> Option<Duration> {
+        match *self {
+            ReplaySpeed::AsFastAsPossible => None,
+            ReplaySpeed::RealTime => Some(offset),
+            ReplaySpeed::Scaled(factor) => Some(offset.div_f64(factor)),
+        }
+    }
+}
+
+/// What a replay went through and how the books coped.
+#[derive(Debug, Clone, Default)]
+pub struct HarnessReport {
+    pub records: u64,
+    pub frames: u64,
+    /// Frames that didn't parse as Kraken messages
+    pub malformed: u64,
+    /// Messages rejected as a whole, e.g. for an unknown symbol
+    pub failed: u64,
+    pub disconnects: u64,
+    pub checkpoints: u64,
+    /// Checkpoints that didn't match the books replayed up to them
+    pub checkpoint_mismatches: u64,
+    pub latency: SymbolLatencyStats,
+    pub quality: QualityStats,
+    pub timing: TimingStats,
+    /// Wall time the replay took, including any pacing
+    pub elapsed: Duration,
+}
+
+/// Replays a capture written by [`Recorder`](crate::recorder::Recorder)
+/// through the same adapter and books as the live loop.
+///
+/// Exchange latency is measured against each frame's recorded receive time,
+/// so replaying a capture twice against the same matchbook gives the same
+/// books and the same report apart from the latency histograms.
+pub struct ReplayHarness<R> {
+    reader: CaptureReader<R>,
+    speed: ReplaySpeed,
+    books: BookSet,
+    adapter: KrakenAdapter,
+    /// Records received before this are applied unpaced and left out of
+    /// the report
+    from: Option<DateTime<Utc>>,
+    /// Replay stops at the first record received after this
+    until: Option<DateTime<Utc>>,
+    /// The next checkpoint is loaded into the books rather than checked
+    /// against them
+    restore: bool,
+}
+
+impl ReplayHarness<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<ReplayHarness<BufReader<File>>, CaptureError> {
+        Ok(ReplayHarness::new(CaptureReader::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> ReplayHarness<R> {
+    /// A harness with a book for every instrument in the capture's header,
+    /// at the precision it was recorded with. Symbols subscribed later get
+    /// a book when their subscription record is replayed.
+    pub fn new(reader: CaptureReader<R>) -> ReplayHarness<R> {
+        let mut books = BookSet::new();
+        for instrument in &reader.header().instruments {
+            books.register_with_spec(instrument.symbol.clone(), instrument.spec());
+        }
+        // Skip bad events like the live loop does, without its audit file
+        books.set_policy(ApplyPolicy::Lenient);
+        ReplayHarness {
+            reader,
+            speed: ReplaySpeed::default(),
+            books,
+            adapter: KrakenAdapter::default(),
+            from: None,
+            until: None,
+            restore: false,
+        }
+    }
+
+    /// Paces the replay, see [`ReplaySpeed`] for why this blocks.
+    pub fn speed(mut self, speed: ReplaySpeed) -> Result<Self, InvalidSpeed> {
+        speed.validate()?;
+        self.speed = speed;
+        Ok(self)
+    }
+
+    /// Replays only the records received from `from` to `to`. The books are
+    /// loaded from the latest checkpoint before `from`, or rebuilt from the
+    /// start of the capture if it has none, and brought up to `from`
+    /// without pacing.
+    pub fn between(mut self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Self, CaptureError> {
+        self.restore = self.reader.seek_to_checkpoint(from)?;
+        self.from = Some(from);
+        self.until = Some(to);
+        Ok(self)
+    }
+
+    pub fn header(&self) -> &CaptureHeader {
+        self.reader.header()
+    }
+
+    pub fn books(&self) -> &BookSet {
+        &self.books
+    }
+
+    pub fn into_books(self) -> BookSet {
+        self.books
+    }
+
+    /// The books replayed into, e.g. to change their mode before running.
+    pub fn books_mut(&mut self) -> &mut BookSet {
+        &mut self.books
+    }
+
+    /// Replays every record left in the capture. A record cut short at the
+    /// end of the file, as left by a crash, ends the replay cleanly.
+    pub fn run(&mut self) -> Result<HarnessReport, CaptureError> {
+        let mut report = HarnessReport::default();
+        let started = Instant::now();
+        // When and at what recorded time the first record in range was applied
+        let mut paced_from = None;
+
+        loop {
+            let record = match self.reader.next_record() {
+                Ok(Some(record)) => record,
+                Ok(None) | Err(CaptureError::Truncated { .. }) => break,
+                Err(e) => return Err(e),
+            };
+            if self.until.is_some_and(|until| record.wall_time() > until) {
+                break;
+            }
+            if self.from.is_some_and(|from| record.wall_time() < from) {
+                self.apply(&record, &mut HarnessReport::default());
+                continue;
+            }
+            report.records += 1;
+
+            let (first_applied, first_mono) =
+                *paced_from.get_or_insert_with(|| (Instant::now(), record.mono_ns));
+            let offset = Duration::from_nanos(record.mono_ns.saturating_sub(first_mono));
+            if let Some(due) = self.speed.due(offset) {
+                std::thread::sleep(due.saturating_sub(first_applied.elapsed()));
+            }
+
+            self.apply(&record, &mut report);
+        }
+
+        report.timing = self.adapter.clock().stats().clone();
+        report.elapsed = started.elapsed();
+        Ok(report)
+    }
+
+    /// Copies the rest of the capture into `output`, adding a checkpoint of
+    /// the replayed books every `every` of recorded time once they are all
+    /// synced. Checkpoints already in the capture are dropped. Returns how
+    /// many were written.
+    pub fn write_checkpoints(
+        &mut self,
+        output: &mut Recorder,
+        every: Duration,
+    ) -> Result<u64, CaptureError> {
+        let mut report = HarnessReport::default();
+        let mut written = 0;
+        let mut last = None;
+
+        loop {
+            let record = match self.reader.next_record() {
+                Ok(Some(record)) => record,
+                Ok(None) | Err(CaptureError::Truncated { .. }) => break,
+                Err(e) => return Err(e),
+            };
+            if record.kind == RecordKind::Checkpoint {
+                continue;
+            }
+            output.write(&record)?;
+            self.apply(&record, &mut report);
+
+            let due = last.is_none_or(|last| {
+                Duration::from_nanos(record.mono_ns.saturating_sub(last)) >= every
+            });
+            let synced = self
+                .books
+                .symbols()
+                .all(|symbol| self.books.is_synced(symbol));
+            if due && synced {
+                let payload =
+                    serde_json::to_vec(&self.books.checkpoint()).map_err(io::Error::from)?;
+                output.write(&Record {
+                    kind: RecordKind::Checkpoint,
+                    payload,
+                    ..record
+                })?;
+                last = Some(record.mono_ns);
+                written += 1;
+            }
+        }
+        Ok(written)
+    }
+
+    fn apply(&mut self, record: &Record, report: &mut HarnessReport) {
+        let response = match record.kind {
+            RecordKind::Frame => {
+                report.frames += 1;
+                match record.text().map(KrakenMessage::parse) {
+                    Some(Ok(KrakenMessage::Snapshot(response)))
+                    | Some(Ok(KrakenMessage::Update(response))) => response,
+                    Some(Ok(_)) => return,
+                    Some(Err(_)) | None => {
+                        report.malformed += 1;
+                        return;
+                    }
+                }
+            }
+            RecordKind::Disconnect => {
+                // The live loop waits for the snapshot on the next connection
+                report.disconnects += 1;
+                self.books.reset();
+                return;
+            }
+            RecordKind::Subscription => {
+                self.subscribe(record, report);
+                return;
+            }
+            RecordKind::Checkpoint => {
+                report.checkpoints += 1;
+                self.checkpoint(record, report);
+                return;
+            }
+        };
+
+        let latency = &mut report.latency;
+        let result =
+            self.adapter
+                .process_at(&mut self.books, &response, record.wall_time(), |event| {
+                    latency.record(&event)
+                });
+        match result {
+            Ok(applied) => {
+                report.quality.record_report(&applied);
+                for mismatch in &applied.mismatches {
+                    // The capture holds the snapshot the live session resubscribed for
+                    self.books.reset_symbol(&mismatch.symbol);
+                }
+            }
+            Err(e) => {
+                if !report.quality.record_error(&e) {
+                    report.failed += 1;
+                }
+            }
+        }
+    }
+
+    /// Registers a book for each newly subscribed symbol, at the precision
+    /// it was recorded with if the record has it.
+    fn subscribe(&mut self, record: &Record, report: &mut HarnessReport) {
+        let Ok(subscribed) = serde_json::from_slice::<SubscriptionRecord>(&record.payload) else {
+            report.malformed += 1;
+            return;
+        };
+        for symbol in &subscribed.subscription.symbols {
+            match subscribed.instrument(symbol) {
+                Some(instrument) => self
+                    .books
+                    .register_with_spec(symbol.clone(), instrument.spec()),
+                None => self.books.register(symbol.clone()),
+            }
+        }
+    }
+
+    /// Loads the checkpoint a range replay starts from, and checks every
+    /// later one against the books.
+    fn checkpoint(&mut self, record: &Record, report: &mut HarnessReport) {
+        let Ok(checkpoint) = serde_json::from_slice::<BookCheckpoint>(&record.payload) else {
+            report.malformed += 1;
+            return;
+        };
+        if std::mem::take(&mut self.restore) {
+            self.books.reset();
+            if self.books.restore(&checkpoint).is_err() {
+                report.failed += 1;
+            }
+        } else if self.books.checkpoint() != checkpoint {
+            report.checkpoint_mismatches += 1;
+        }
+    }
+}
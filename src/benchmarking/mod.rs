@@ -1,3 +1,6 @@
+pub mod harness;
+pub mod query;
+pub mod recorder;
 pub mod stats;
 
 pub use stats::*;
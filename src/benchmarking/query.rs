@@ -0,0 +1,65 @@
+use crate::book::Book;
+use crate::checkpoint::OrderDiff;
+use crate::harness::ReplayHarness;
+use crate::recorder::{CaptureError, CaptureHeader, CaptureReader};
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, thiserror::Error)]
+pub enum QueryError {
+    #[error(transparent)]
+    Capture(#[from] CaptureError),
+    #[error("{0} is not in the capture")]
+    UnknownSymbol(String),
+    #[error("Range ends at {to} before it starts at {from}")]
+    InvalidRange {
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    },
+}
+
+/// Rebuilds books as they stood at points in a capture, replaying from the
+/// nearest checkpoint before each point.
+pub struct CaptureQuery {
+    path: PathBuf,
+    header: CaptureHeader,
+}
+
+impl CaptureQuery {
+    pub fn open(path: impl AsRef<Path>) -> Result<CaptureQuery, QueryError> {
+        let path = path.as_ref().to_path_buf();
+        let header = CaptureReader::open(&path)?.header().clone();
+        Ok(CaptureQuery { path, header })
+    }
+
+    pub fn header(&self) -> &CaptureHeader {
+        &self.header
+    }
+
+    /// The book for `symbol` with every record received up to and including
+    /// `at` applied. It is unsynced if no snapshot had arrived by then.
+    pub fn book_at(&self, symbol: &str, at: DateTime<Utc>) -> Result<Book, QueryError> {
+        let mut harness = ReplayHarness::open(&self.path)?.between(at, at)?;
+        harness.run()?;
+        harness
+            .into_books()
+            .remove(symbol)
+            .ok_or_else(|| QueryError::UnknownSymbol(symbol.to_string()))
+    }
+
+    /// The orders added, removed and resized in `symbol`'s book between
+    /// `from` and `to`.
+    pub fn diff(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<OrderDiff, QueryError> {
+        if to < from {
+            return Err(QueryError::InvalidRange { from, to });
+        }
+        let before = self.book_at(symbol, from)?.checkpoint(symbol);
+        let after = self.book_at(symbol, to)?.checkpoint(symbol);
+        Ok(before.diff(&after))
+    }
+}
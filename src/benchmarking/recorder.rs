@@ -0,0 +1,948 @@
+use crate::checkpoint::BookCheckpoint;
+use crate::instrument::InstrumentSpec;
+use crate::subscription::SubscriptionConfig;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// First bytes of every capture file.
+pub const CAPTURE_MAGIC: [u8; 8] = *b"MSTKCAP\0";
+
+/// Capture format written by this build.
+///
+/// A capture is the magic, the version as a little-endian `u16`, then the
+/// [`CaptureHeader`] as JSON behind a `u32` length. Records follow, each a
+/// `u32` length and then the kind byte, monotonic and wall-clock nanoseconds,
+/// connection id and payload, see [`Record`]. All integers are little-endian.
+///
+/// Version 1 wrote records straight to the file. Since version 2 they are
+/// grouped into zstd compressed blocks, each a `B` tag, the compressed and
+/// raw lengths and the record count as `u32`s, then the compressed records.
+/// A finished capture ends with an `I` tag, the [`BlockIndex`] of every
+/// block, and a trailer of the index's offset as a `u64` and
+/// [`INDEX_MAGIC`]. A capture cut off before its index is still read block
+/// by block, it just can't seek.
+///
+/// Version 3 added [`RecordKind::Checkpoint`] records. Each one starts a new
+/// block, and a flag byte at the end of every index entry marks those blocks
+/// so a replay can find the checkpoint nearest a time without decoding.
+/// Subscription records written since then carry the precision of each
+/// symbol, see [`SubscriptionRecord`]; older ones read with none.
+pub const CAPTURE_VERSION: u16 = 3;
+
+/// Last bytes of a capture with a block index.
+pub const INDEX_MAGIC: [u8; 8] = *b"MSTKIDX\0";
+
+/// Raw bytes of records gathered before a block is compressed and written.
+const BLOCK_SIZE: usize = 256 * 1024;
+
+/// Longest stretch of feed one block covers, which bounds how far a seek
+/// has to decode past the time it was asked for.
+const BLOCK_SPAN: Duration = Duration::from_secs(1);
+
+const BLOCK_TAG: u8 = b'B';
+const INDEX_TAG: u8 = b'I';
+
+/// zstd's default level; captures are written while the feed is live.
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Offset, record count, first and last monotonic and wall-clock times, and
+/// since version 3 the checkpoint flag.
+fn index_entry_len(version: u16) -> usize {
+    let len = 8 + 4 + 8 + 8 + 8 + 8;
+    if version >= 3 { len + 1 } else { len }
+}
+
+/// Index offset and [`INDEX_MAGIC`].
+const TRAILER_LEN: i64 = 8 + 8;
+
+/// Records or headers longer than this are treated as corruption rather
+/// than allocated.
+const MAX_RECORD_LEN: u32 = 64 * 1024 * 1024;
+
+/// Kind byte, monotonic ns, wall-clock ns and connection id.
+const RECORD_PREFIX_LEN: u32 = 1 + 8 + 8 + 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CaptureError {
+    #[error("Capture I/O failed")]
+    Io(#[from] io::Error),
+    #[error("Not a matchstick capture")]
+    NotACapture,
+    #[error("Unsupported capture version {0}")]
+    UnsupportedVersion(u16),
+    #[error("Invalid capture header")]
+    InvalidHeader(#[from] serde_json::Error),
+    #[error("Unknown record kind {kind} at byte {offset}")]
+    UnknownRecordKind { kind: u8, offset: u64 },
+    #[error("Corrupt record at byte {offset}")]
+    Corrupt { offset: u64 },
+    #[error("Capture ends partway through the record at byte {offset}")]
+    Truncated { offset: u64 },
+    #[error("Only version {CAPTURE_VERSION} captures can be appended to, not version {0}")]
+    CannotAppend(u16),
+}
+
+/// Precision a symbol was mirrored at when the capture was taken.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstrumentHeader {
+    pub symbol: String,
+    pub price_precision: u32,
+    pub qty_precision: u32,
+    pub tick_size: u64,
+}
+
+impl InstrumentHeader {
+    pub fn new(symbol: impl Into<String>, spec: InstrumentSpec) -> InstrumentHeader {
+        InstrumentHeader {
+            symbol: symbol.into(),
+            price_precision: spec.price_precision,
+            qty_precision: spec.qty_precision,
+            tick_size: spec.tick_size,
+        }
+    }
+
+    pub fn spec(&self) -> InstrumentSpec {
+        InstrumentSpec::new(self.price_precision, self.qty_precision).with_tick_size(self.tick_size)
+    }
+
+    /// Headers for Kraken symbols at the precision each pair is mirrored at.
+    fn kraken(symbols: &[String]) -> Vec<InstrumentHeader> {
+        symbols
+            .iter()
+            .map(|symbol| {
+                let spec = InstrumentSpec::for_symbol(symbol).unwrap_or_default();
+                InstrumentHeader::new(symbol.clone(), spec)
+            })
+            .collect()
+    }
+}
+
+/// Payload of a [`RecordKind::Subscription`] record: the subscription and
+/// the precision of each of its symbols, so symbols added after the header
+/// replay at the precision they were mirrored at.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubscriptionRecord {
+    #[serde(flatten)]
+    pub subscription: SubscriptionConfig,
+    #[serde(default)]
+    pub instruments: Vec<InstrumentHeader>,
+}
+
+impl SubscriptionRecord {
+    pub fn kraken(subscription: &SubscriptionConfig) -> SubscriptionRecord {
+        SubscriptionRecord {
+            subscription: subscription.clone(),
+            instruments: InstrumentHeader::kraken(&subscription.symbols),
+        }
+    }
+
+    pub fn instrument(&self, symbol: &str) -> Option<&InstrumentHeader> {
+        self.instruments
+            .iter()
+            .find(|instrument| instrument.symbol == symbol)
+    }
+}
+
+/// What a capture was recorded from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureHeader {
+    pub venue: String,
+    pub subscription: SubscriptionConfig,
+    pub instruments: Vec<InstrumentHeader>,
+    pub started_at: DateTime<Utc>,
+}
+
+impl CaptureHeader {
+    /// A header for a Kraken session subscribed with `subscription`, at the
+    /// precision each pair is mirrored at.
+    pub fn kraken(subscription: &SubscriptionConfig) -> CaptureHeader {
+        CaptureHeader {
+            venue: "kraken".to_string(),
+            subscription: subscription.clone(),
+            instruments: InstrumentHeader::kraken(&subscription.symbols),
+            started_at: Utc::now(),
+        }
+    }
+
+    pub fn instrument(&self, symbol: &str) -> Option<&InstrumentHeader> {
+        self.instruments
+            .iter()
+            .find(|instrument| instrument.symbol == symbol)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum RecordKind {
+    /// A text frame exactly as the venue sent it
+    Frame = 1,
+    /// The subscription in force on the connection, as a JSON
+    /// [`SubscriptionRecord`]
+    Subscription = 2,
+    /// The connection closed or failed; the payload is empty
+    Disconnect = 3,
+    /// Every book as of this point in the capture, as a JSON
+    /// [`BookCheckpoint`]
+    Checkpoint = 4,
+}
+
+impl RecordKind {
+    pub fn from_u8(kind: u8) -> Option<RecordKind> {
+        match kind {
+            1 => Some(RecordKind::Frame),
+            2 => Some(RecordKind::Subscription),
+            3 => Some(RecordKind::Disconnect),
+            4 => Some(RecordKind::Checkpoint),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub kind: RecordKind,
+    /// Nanoseconds since the recorder started, from a monotonic clock
+    pub mono_ns: u64,
+    /// Wall-clock receive time, in nanoseconds since the Unix epoch
+    pub wall_ns: i64,
+    /// Counts up from 1 each time the client opens a new socket
+    pub connection: u32,
+    pub payload: Vec<u8>,
+}
+
+impl Record {
+    pub fn text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.payload).ok()
+    }
+
+    pub fn wall_time(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_nanos(self.wall_ns)
+    }
+}
+
+/// Where one compressed block sits in a capture and the records it holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockIndex {
+    /// Byte offset of the block's tag
+    pub offset: u64,
+    pub records: u32,
+    pub first_mono_ns: u64,
+    pub last_mono_ns: u64,
+    pub first_wall_ns: i64,
+    pub last_wall_ns: i64,
+    /// The block starts with a [`RecordKind::Checkpoint`]
+    pub checkpoint: bool,
+}
+
+impl BlockIndex {
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&self.offset.to_le_bytes())?;
+        writer.write_all(&self.records.to_le_bytes())?;
+        writer.write_all(&self.first_mono_ns.to_le_bytes())?;
+        writer.write_all(&self.last_mono_ns.to_le_bytes())?;
+        writer.write_all(&self.first_wall_ns.to_le_bytes())?;
+        writer.write_all(&self.last_wall_ns.to_le_bytes())?;
+        writer.write_all(&[self.checkpoint as u8])
+    }
+
+    fn from_bytes(bytes: &[u8]) -> BlockIndex {
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        BlockIndex {
+            offset: u64_at(0),
+            records: u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+            first_mono_ns: u64_at(12),
+            last_mono_ns: u64_at(20),
+            first_wall_ns: u64_at(28) as i64,
+            last_wall_ns: u64_at(36) as i64,
+            checkpoint: bytes.get(44) == Some(&1),
+        }
+    }
+}
+
+/// Appends raw feed frames to a capture file so a live session can be
+/// replayed later.
+///
+/// Records are buffered into blocks and compressed; [`Recorder::flush`]
+/// writes out a partial block and [`Recorder::finish`], or dropping the
+/// recorder, adds the block index.
+#[derive(Debug)]
+pub struct Recorder {
+    writer: BufWriter<File>,
+    header: CaptureHeader,
+    started: Instant,
+    /// Monotonic time already covered by an earlier session in the same file
+    mono_base: u64,
+    records: u64,
+    /// Offset the next block will be written at
+    offset: u64,
+    block: Vec<u8>,
+    pending: Option<BlockIndex>,
+    index: Vec<BlockIndex>,
+    finished: bool,
+}
+
+impl Recorder {
+    /// Starts a new capture at `path`, replacing any file already there.
+    pub fn create(path: impl AsRef<Path>, header: CaptureHeader) -> Result<Recorder, CaptureError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&CAPTURE_MAGIC)?;
+        writer.write_all(&CAPTURE_VERSION.to_le_bytes())?;
+        let json = serde_json::to_vec(&header)?;
+        writer.write_all(&(json.len() as u32).to_le_bytes())?;
+        writer.write_all(&json)?;
+        writer.flush()?;
+        Ok(Recorder {
+            writer,
+            header,
+            started: Instant::now(),
+            mono_base: 0,
+            records: 0,
+            offset: 14 + json.len() as u64,
+            block: Vec::new(),
+            pending: None,
+            index: Vec::new(),
+            finished: false,
+        })
+    }
+
+    /// Reopens an existing capture to add records after its last complete
+    /// block. The index, and any block cut short by a crash, are dropped
+    /// first and the index is rewritten when the recorder finishes.
+    pub fn append(path: impl AsRef<Path>) -> Result<Recorder, CaptureError> {
+        let mut reader = CaptureReader::open(&path)?;
+        if reader.version() != CAPTURE_VERSION {
+            return Err(CaptureError::CannotAppend(reader.version()));
+        }
+        let index = reader.scan_blocks()?;
+        let end = reader.offset();
+        let header = reader.header().clone();
+
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        file.set_len(end)?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(Recorder {
+            writer: BufWriter::new(file),
+            header,
+            started: Instant::now(),
+            mono_base: index.last().map_or(0, |block| block.last_mono_ns),
+            records: index.iter().map(|block| block.records as u64).sum(),
+            offset: end,
+            block: Vec::new(),
+            pending: None,
+            index,
+            finished: false,
+        })
+    }
+
+    pub fn header(&self) -> &CaptureHeader {
+        &self.header
+    }
+
+    /// Records written to the file, including those from earlier sessions.
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    pub fn record_frame(&mut self, connection: u32, frame: &str) -> io::Result<()> {
+        self.record(RecordKind::Frame, connection, frame.as_bytes())
+    }
+
+    /// Records the subscription along with the precision of its symbols.
+    pub fn record_subscription(
+        &mut self,
+        connection: u32,
+        subscription: &SubscriptionConfig,
+    ) -> io::Result<()> {
+        let json = serde_json::to_vec(&SubscriptionRecord::kraken(subscription))?;
+        self.record(RecordKind::Subscription, connection, &json)
+    }
+
+    pub fn record_disconnect(&mut self, connection: u32) -> io::Result<()> {
+        self.record(RecordKind::Disconnect, connection, &[])
+    }
+
+    /// Records the state of every book, which must reflect exactly the
+    /// frames recorded so far.
+    pub fn record_checkpoint(
+        &mut self,
+        connection: u32,
+        checkpoint: &BookCheckpoint,
+    ) -> io::Result<()> {
+        let json = serde_json::to_vec(checkpoint)?;
+        self.record(RecordKind::Checkpoint, connection, &json)
+    }
+
+    /// Appends one record stamped with the current monotonic and wall-clock
+    /// time.
+    pub fn record(&mut self, kind: RecordKind, connection: u32, payload: &[u8]) -> io::Result<()> {
+        self.write(&Record {
+            kind,
+            mono_ns: self.mono_base + self.started.elapsed().as_nanos() as u64,
+            wall_ns: Utc::now().timestamp_nanos_opt().unwrap_or_default(),
+            connection,
+            payload: payload.to_vec(),
+        })
+    }
+
+    /// Appends a record keeping its timestamps, e.g. one copied from
+    /// another capture.
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        let checkpoint = record.kind == RecordKind::Checkpoint;
+        if checkpoint {
+            self.write_block()?;
+        }
+
+        let len = RECORD_PREFIX_LEN + record.payload.len() as u32;
+        self.block.extend_from_slice(&len.to_le_bytes());
+        self.block.push(record.kind as u8);
+        self.block.extend_from_slice(&record.mono_ns.to_le_bytes());
+        self.block.extend_from_slice(&record.wall_ns.to_le_bytes());
+        self.block
+            .extend_from_slice(&record.connection.to_le_bytes());
+        self.block.extend_from_slice(&record.payload);
+        self.records += 1;
+
+        let block = self.pending.get_or_insert(BlockIndex {
+            offset: self.offset,
+            records: 0,
+            first_mono_ns: record.mono_ns,
+            last_mono_ns: record.mono_ns,
+            first_wall_ns: record.wall_ns,
+            last_wall_ns: record.wall_ns,
+            checkpoint,
+        });
+        block.records += 1;
+        block.last_mono_ns = record.mono_ns;
+        block.last_wall_ns = record.wall_ns;
+
+        let span = Duration::from_nanos(block.last_mono_ns.saturating_sub(block.first_mono_ns));
+        if self.block.len() >= BLOCK_SIZE || span >= BLOCK_SPAN {
+            self.write_block()?;
+        }
+        Ok(())
+    }
+
+    /// Writes out the records buffered so far as a block.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.write_block()?;
+        self.writer.flush()
+    }
+
+    /// Writes the last block and the block index. The capture can still be
+    /// reopened with [`Recorder::append`].
+    pub fn finish(mut self) -> io::Result<()> {
+        self.write_index()
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        let Some(block) = self.pending.take() else {
+            return Ok(());
+        };
+        let compressed = zstd::bulk::compress(&self.block, COMPRESSION_LEVEL)?;
+        self.writer.write_all(&[BLOCK_TAG])?;
+        self.writer
+            .write_all(&(compressed.len() as u32).to_le_bytes())?;
+        self.writer
+            .write_all(&(self.block.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&block.records.to_le_bytes())?;
+        self.writer.write_all(&compressed)?;
+
+        self.offset += BLOCK_HEADER_LEN + compressed.len() as u64;
+        self.block.clear();
+        self.index.push(block);
+        Ok(())
+    }
+
+    fn write_index(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        self.write_block()?;
+
+        let index_offset = self.offset;
+        self.writer.write_all(&[INDEX_TAG])?;
+        self.writer
+            .write_all(&(self.index.len() as u32).to_le_bytes())?;
+        for block in &self.index {
+            block.write_to(&mut self.writer)?;
+        }
+        self.writer.write_all(&index_offset.to_le_bytes())?;
+        self.writer.write_all(&INDEX_MAGIC)?;
+        self.writer.flush()
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.write_index();
+    }
+}
+
+/// Tag, compressed length, raw length and record count.
+const BLOCK_HEADER_LEN: u64 = 1 + 4 + 4 + 4;
+
+/// Reads a capture written by [`Recorder`], one record at a time.
+#[derive(Debug)]
+pub struct CaptureReader<R> {
+    reader: R,
+    header: CaptureHeader,
+    version: u16,
+    /// Offset of the first record or block
+    data_start: u64,
+    /// Offset of the next record (version 1) or block
+    offset: u64,
+    /// Decompressed records of the current block
+    block: Vec<u8>,
+    block_pos: usize,
+    index: Option<Vec<BlockIndex>>,
+    index_offset: Option<u64>,
+    /// Records received before this wall-clock time are skipped
+    skip_before: Option<i64>,
+    /// Set once a record fails to read, ending iteration
+    failed: bool,
+}
+
+impl CaptureReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<CaptureReader<BufReader<File>>, CaptureError> {
+        CaptureReader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> CaptureReader<R> {
+    /// Reads and checks the magic, version and header, and the block index
+    /// if the capture has one.
+    pub fn new(mut reader: R) -> Result<CaptureReader<R>, CaptureError> {
+        let mut magic = [0; 8];
+        reader
+            .read_exact(&mut magic)
+            .map_err(|_| CaptureError::NotACapture)?;
+        if magic != CAPTURE_MAGIC {
+            return Err(CaptureError::NotACapture);
+        }
+
+        let mut version = [0; 2];
+        reader
+            .read_exact(&mut version)
+            .map_err(|_| CaptureError::NotACapture)?;
+        let version = u16::from_le_bytes(version);
+        if !(1..=CAPTURE_VERSION).contains(&version) {
+            return Err(CaptureError::UnsupportedVersion(version));
+        }
+
+        let mut len = [0; 4];
+        reader
+            .read_exact(&mut len)
+            .map_err(|_| CaptureError::NotACapture)?;
+        let len = u32::from_le_bytes(len);
+        if len > MAX_RECORD_LEN {
+            return Err(CaptureError::Corrupt { offset: 10 });
+        }
+        let mut json = vec![0; len as usize];
+        reader
+            .read_exact(&mut json)
+            .map_err(|_| CaptureError::Truncated { offset: 10 })?;
+        let header = serde_json::from_slice(&json)?;
+
+        let data_start = 14 + len as u64;
+        let mut capture = CaptureReader {
+            reader,
+            header,
+            version,
+            data_start,
+            offset: data_start,
+            block: Vec::new(),
+            block_pos: 0,
+            index: None,
+            index_offset: None,
+            skip_before: None,
+            failed: false,
+        };
+        if version >= 2 {
+            capture.read_index()?;
+        }
+        Ok(capture)
+    }
+
+    pub fn header(&self) -> &CaptureHeader {
+        &self.header
+    }
+
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// Byte offset of the next record, or of the next block since version 2.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The block index, if the capture was finished with one.
+    pub fn index(&self) -> Option<&[BlockIndex]> {
+        self.index.as_deref()
+    }
+
+    /// Moves to the first record received at or after `at`. With a block
+    /// index only the block holding `at` is decoded; otherwise the capture is
+    /// read from the start and earlier records skipped.
+    pub fn seek_to(&mut self, at: DateTime<Utc>) -> Result<(), CaptureError> {
+        let at = at.timestamp_nanos_opt().unwrap_or(i64::MAX);
+        let start = match (&self.index, self.index_offset) {
+            (Some(index), Some(index_offset)) => index
+                .iter()
+                .find(|block| block.last_wall_ns >= at)
+                .map_or(index_offset, |block| block.offset),
+            _ => self.data_start,
+        };
+        self.reader.seek(SeekFrom::Start(start))?;
+        self.offset = start;
+        self.block.clear();
+        self.block_pos = 0;
+        self.skip_before = Some(at);
+        self.failed = false;
+        Ok(())
+    }
+
+    /// Moves to the latest checkpoint taken at or before `at`, so replaying
+    /// from there rebuilds the books as they were at `at`. Without a block
+    /// index or an earlier checkpoint this is the start of the capture.
+    /// Returns whether a checkpoint was found.
+    pub fn seek_to_checkpoint(&mut self, at: DateTime<Utc>) -> Result<bool, CaptureError> {
+        let at = at.timestamp_nanos_opt().unwrap_or(i64::MAX);
+        let checkpoint = self.index.as_ref().and_then(|index| {
+            index
+                .iter()
+                .rfind(|block| block.checkpoint && block.first_wall_ns <= at)
+                .map(|block| block.offset)
+        });
+        let start = checkpoint.unwrap_or(self.data_start);
+        self.reader.seek(SeekFrom::Start(start))?;
+        self.offset = start;
+        self.block.clear();
+        self.block_pos = 0;
+        self.skip_before = None;
+        self.failed = false;
+        Ok(checkpoint.is_some())
+    }
+
+    /// The next record, or `None` at a clean end of file.
+    pub fn next_record(&mut self) -> Result<Option<Record>, CaptureError> {
+        loop {
+            let record = match self.version {
+                1 => self.read_record()?,
+                _ => self.read_block_record()?,
+            };
+            match record {
+                Some(record) if self.skip_before.is_some_and(|at| record.wall_ns < at) => continue,
+                record => return Ok(record),
+            }
+        }
+    }
+
+    /// Reads a version 1 record straight from the file.
+    fn read_record(&mut self) -> Result<Option<Record>, CaptureError> {
+        let offset = self.offset;
+        let mut len = [0; 4];
+        match read_full(&mut self.reader, &mut len)? {
+            0 => return Ok(None),
+            4 => {}
+            _ => return Err(CaptureError::Truncated { offset }),
+        }
+        let len = u32::from_le_bytes(len);
+        if !(RECORD_PREFIX_LEN..=MAX_RECORD_LEN).contains(&len) {
+            return Err(CaptureError::Corrupt { offset });
+        }
+
+        let mut body = vec![0; len as usize];
+        if read_full(&mut self.reader, &mut body)? < body.len() {
+            return Err(CaptureError::Truncated { offset });
+        }
+        self.offset += 4 + len as u64;
+        parse_record(&body, offset).map(Some)
+    }
+
+    /// Reads the next record from the current block, decoding the next
+    /// block once it runs out.
+    fn read_block_record(&mut self) -> Result<Option<Record>, CaptureError> {
+        if self.block_pos >= self.block.len() {
+            self.block_pos = 0;
+            self.block.clear();
+            if !self.read_block()? {
+                return Ok(None);
+            }
+        }
+
+        let offset = self.offset;
+        let rest = &self.block[self.block_pos..];
+        let len = rest
+            .get(..4)
+            .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+            .ok_or(CaptureError::Corrupt { offset })?;
+        let body = rest
+            .get(4..4 + len)
+            .filter(|body| body.len() >= RECORD_PREFIX_LEN as usize)
+            .ok_or(CaptureError::Corrupt { offset })?;
+        let record = parse_record(body, offset)?;
+        self.block_pos += 4 + len;
+        Ok(Some(record))
+    }
+
+    /// Decodes the block at the current offset. Returns false at the end of
+    /// the blocks.
+    fn read_block(&mut self) -> Result<bool, CaptureError> {
+        let offset = self.offset;
+        let mut header = [0; BLOCK_HEADER_LEN as usize];
+        match read_full(&mut self.reader, &mut header)? {
+            0 => return Ok(false),
+            _ if header[0] == INDEX_TAG => return Ok(false),
+            _ if header[0] != BLOCK_TAG => return Err(CaptureError::Corrupt { offset }),
+            n if n < header.len() => return Err(CaptureError::Truncated { offset }),
+            _ => {}
+        }
+        let compressed_len = u32::from_le_bytes(header[1..5].try_into().unwrap());
+        let raw_len = u32::from_le_bytes(header[5..9].try_into().unwrap());
+        if compressed_len > MAX_RECORD_LEN || raw_len > MAX_RECORD_LEN {
+            return Err(CaptureError::Corrupt { offset });
+        }
+
+        let mut compressed = vec![0; compressed_len as usize];
+        if read_full(&mut self.reader, &mut compressed)? < compressed.len() {
+            return Err(CaptureError::Truncated { offset });
+        }
+        self.block = zstd::bulk::decompress(&compressed, raw_len as usize)
+            .map_err(|_| CaptureError::Corrupt { offset })?;
+        self.offset += BLOCK_HEADER_LEN + compressed_len as u64;
+        Ok(true)
+    }
+
+    /// Loads the block index from the end of the file, if there is one.
+    fn read_index(&mut self) -> Result<(), CaptureError> {
+        let end = self.reader.seek(SeekFrom::End(0))?;
+        if end < self.data_start + TRAILER_LEN as u64 {
+            self.reader.seek(SeekFrom::Start(self.data_start))?;
+            return Ok(());
+        }
+
+        let mut trailer = [0; TRAILER_LEN as usize];
+        self.reader.seek(SeekFrom::End(-TRAILER_LEN))?;
+        self.reader.read_exact(&mut trailer)?;
+        let index_offset = u64::from_le_bytes(trailer[..8].try_into().unwrap());
+        if trailer[8..] == INDEX_MAGIC && (self.data_start..end).contains(&index_offset) {
+            let mut entries = [0; 5];
+            self.reader.seek(SeekFrom::Start(index_offset))?;
+            self.reader.read_exact(&mut entries)?;
+            if entries[0] != INDEX_TAG {
+                return Err(CaptureError::Corrupt {
+                    offset: index_offset,
+                });
+            }
+            let count = u32::from_le_bytes(entries[1..].try_into().unwrap()) as usize;
+            let entry_len = index_entry_len(self.version);
+            if count * entry_len > (end - index_offset) as usize {
+                return Err(CaptureError::Corrupt {
+                    offset: index_offset,
+                });
+            }
+            let mut bytes = vec![0; count * entry_len];
+            self.reader.read_exact(&mut bytes)?;
+            self.index = Some(
+                bytes
+                    .chunks_exact(entry_len)
+                    .map(BlockIndex::from_bytes)
+                    .collect(),
+            );
+            self.index_offset = Some(index_offset);
+        }
+
+        self.reader.seek(SeekFrom::Start(self.data_start))?;
+        Ok(())
+    }
+
+    /// The index of every complete block, read from the footer or by walking
+    /// the blocks. Leaves the reader at the end of the last complete block.
+    fn scan_blocks(&mut self) -> Result<Vec<BlockIndex>, CaptureError> {
+        if let (Some(index), Some(index_offset)) = (self.index.clone(), self.index_offset) {
+            self.offset = index_offset;
+            return Ok(index);
+        }
+
+        let mut index = Vec::new();
+        loop {
+            let offset = self.offset;
+            match self.read_block() {
+                Ok(true) => {}
+                Ok(false) | Err(CaptureError::Truncated { .. }) => {
+                    self.offset = offset;
+                    return Ok(index);
+                }
+                Err(e) => return Err(e),
+            }
+
+            let mut block: Option<BlockIndex> = None;
+            while self.block_pos < self.block.len() {
+                let record = self
+                    .read_block_record()?
+                    .ok_or(CaptureError::Corrupt { offset })?;
+                let entry = block.get_or_insert(BlockIndex {
+                    offset,
+                    records: 0,
+                    first_mono_ns: record.mono_ns,
+                    last_mono_ns: record.mono_ns,
+                    first_wall_ns: record.wall_ns,
+                    last_wall_ns: record.wall_ns,
+                    checkpoint: record.kind == RecordKind::Checkpoint,
+                });
+                entry.records += 1;
+                entry.last_mono_ns = record.mono_ns;
+                entry.last_wall_ns = record.wall_ns;
+            }
+            self.block.clear();
+            self.block_pos = 0;
+            index.extend(block);
+        }
+    }
+}
+
+impl<R: Read + Seek> Iterator for CaptureReader<R> {
+    type Item = Result<Record, CaptureError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let next = self.next_record().transpose();
+        self.failed = matches!(next, Some(Err(_)));
+        next
+    }
+}
+
+/// Decodes a record from its bytes after the length.
+fn parse_record(body: &[u8], offset: u64) -> Result<Record, CaptureError> {
+    let kind = RecordKind::from_u8(body[0]).ok_or(CaptureError::UnknownRecordKind {
+        kind: body[0],
+        offset,
+    })?;
+    Ok(Record {
+        kind,
+        mono_ns: u64::from_le_bytes(body[1..9].try_into().unwrap()),
+        wall_ns: i64::from_le_bytes(body[9..17].try_into().unwrap()),
+        connection: u32::from_le_bytes(body[17..21].try_into().unwrap()),
+        payload: body[21..].to_vec(),
+    })
+}
+
+/// Reads until `buf` is full or the reader runs out, returning how much was read.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// What `matchstick capture info` reports about a capture.
+#[derive(Debug, Clone, Default)]
+pub struct CaptureInfo {
+    pub version: u16,
+    pub venue: String,
+    /// Symbols in the header or in any later subscription record
+    pub symbols: BTreeSet<String>,
+    pub records: u64,
+    pub disconnects: u64,
+    pub checkpoints: u64,
+    /// Frames by channel and message type, e.g. `level3 update`
+    pub messages: BTreeMap<String, u64>,
+    pub first: Option<DateTime<Utc>>,
+    pub last: Option<DateTime<Utc>>,
+    /// Blocks in the index, or `None` for a capture without one
+    pub blocks: Option<usize>,
+    /// A record cut short at the end, as left by a crash
+    pub truncated: bool,
+}
+
+impl CaptureInfo {
+    /// Reads the whole capture to count its messages.
+    pub fn read(path: impl AsRef<Path>) -> Result<CaptureInfo, CaptureError> {
+        #[derive(Deserialize)]
+        struct Envelope {
+            channel: Option<String>,
+            method: Option<String>,
+            #[serde(rename = "type")]
+            message_type: Option<String>,
+        }
+
+        let mut reader = CaptureReader::open(path)?;
+        let header = reader.header();
+        let mut info = CaptureInfo {
+            version: reader.version(),
+            venue: header.venue.clone(),
+            symbols: header.subscription.symbols.iter().cloned().collect(),
+            blocks: reader.index().map(<[BlockIndex]>::len),
+            ..CaptureInfo::default()
+        };
+
+        loop {
+            let record = match reader.next_record() {
+                Ok(Some(record)) => record,
+                Ok(None) => break,
+                Err(CaptureError::Truncated { .. }) => {
+                    info.truncated = true;
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+            info.records += 1;
+            info.first.get_or_insert(record.wall_time());
+            info.last = Some(record.wall_time());
+
+            match record.kind {
+                RecordKind::Frame => {
+                    let kind = match serde_json::from_slice::<Envelope>(&record.payload) {
+                        Ok(Envelope {
+                            channel: Some(channel),
+                            message_type: Some(message_type),
+                            ..
+                        }) => format!("{} {}", channel, message_type),
+                        Ok(Envelope {
+                            channel: Some(name),
+                            ..
+                        })
+                        | Ok(Envelope {
+                            method: Some(name), ..
+                        }) => name,
+                        _ => "malformed".to_string(),
+                    };
+                    *info.messages.entry(kind).or_default() += 1;
+                }
+                RecordKind::Subscription => {
+                    if let Ok(record) =
+                        serde_json::from_slice::<SubscriptionRecord>(&record.payload)
+                    {
+                        info.symbols.extend(record.subscription.symbols);
+                    }
+                }
+                RecordKind::Disconnect => info.disconnects += 1,
+                RecordKind::Checkpoint => info.checkpoints += 1,
+            }
+        }
+        Ok(info)
+    }
+
+    /// Time between the first and last record.
+    pub fn span(&self) -> Duration {
+        match (self.first, self.last) {
+            (Some(first), Some(last)) => (last - first).to_std().unwrap_or_default(),
+            _ => Duration::ZERO,
+        }
+    }
+}
@@ -1,31 +1,169 @@
+use crate::applier::{ApplyReport, EventOutcome, Outcome, ParseError};
 use hdrhistogram::Histogram;
+use std::collections::BTreeMap;
 use std::time::Duration;
 
 #[derive(Debug, Clone)]
 pub struct LatencyStats {
     add_order: Histogram<u64>,
+    modify_order: Histogram<u64>,
     cancel_order: Histogram<u64>,
     match_order: Histogram<u64>,
 }
 
-#[allow(dead_code)]
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionStats {
+    pub reconnects: u32,
+    /// Total time between losing the feed and the first snapshot on a new connection.
+    pub stale: Duration,
+}
+
+impl ConnectionStats {
+    pub fn record_stale(&mut self, value: Duration) {
+        self.stale += value;
+    }
+}
+
+/// Latency stats kept separately for each symbol of a multi-symbol session.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolLatencyStats {
+    symbols: BTreeMap<String, LatencyStats>,
+}
+
+impl SymbolLatencyStats {
+    pub fn new() -> SymbolLatencyStats {
+        SymbolLatencyStats::default()
+    }
+
+    pub fn symbol(&mut self, symbol: &str) -> &mut LatencyStats {
+        self.symbols.entry(symbol.to_string()).or_default()
+    }
+
+    /// Records an applied event against its symbol.
+    pub fn record(&mut self, event: &EventOutcome<'_>) {
+        self.symbol(event.symbol).record_outcome(event);
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<&LatencyStats> {
+        self.symbols.get(symbol)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &LatencyStats)> {
+        self.symbols
+            .iter()
+            .map(|(symbol, stats)| (symbol.as_str(), stats))
+    }
+}
+
+/// Counts of problems found in the feed itself rather than in the connection.
+#[derive(Debug, Clone, Default)]
+pub struct QualityStats {
+    pub checksum_mismatches: u64,
+    pub resubscribes: u64,
+    /// Events dropped for a price or quantity that didn't fit the pair
+    pub invalid_values: u64,
+    /// Updates dropped because their book hadn't loaded a snapshot yet
+    pub unsynced_updates: u64,
+    /// Events the orderbook refused, e.g. a delete of an unknown order
+    pub rejected_events: u64,
+    /// Applied events that looked inconsistent, see the audit log
+    pub anomalies: u64,
+}
+
+impl QualityStats {
+    /// Counts the events a lenient policy skipped, the anomalies it saw and
+    /// the books that failed their checksum.
+    pub fn record_report(&mut self, report: &ApplyReport) {
+        for skipped in &report.errors {
+            match skipped.error {
+                ParseError::InvalidPrice { .. } | ParseError::InvalidQuantity { .. } => {
+                    self.invalid_values += 1
+                }
+                _ => self.rejected_events += 1,
+            }
+        }
+        self.anomalies += report.anomalies.len() as u64;
+        self.checksum_mismatches += report.mismatches.len() as u64;
+    }
+
+    /// Counts a message rejected as a whole if the feed was at fault.
+    /// Returns false for other errors, e.g. an unregistered symbol.
+    pub fn record_error(&mut self, error: &ParseError) -> bool {
+        match error {
+            ParseError::UpdateBeforeSnapshot(_) => {
+                self.unsynced_updates += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// How the venue's own event timestamps behaved, see
+/// [`FeedClock`](crate::timing::FeedClock).
+#[derive(Debug, Clone)]
+pub struct TimingStats {
+    /// Updates stamped earlier than one already seen for their symbol
+    pub out_of_order: u64,
+    /// Silences longer than the gap threshold
+    pub gaps: u64,
+    pub largest_gap: Duration,
+    /// Exchange timestamp to local receive time
+    latency: Histogram<u64>,
+}
+
+impl TimingStats {
+    pub fn new() -> TimingStats {
+        TimingStats {
+            out_of_order: 0,
+            gaps: 0,
+            largest_gap: Duration::ZERO,
+            latency: Histogram::new(3).unwrap(),
+        }
+    }
+
+    pub fn record_latency(&mut self, value: Duration) {
+        self.latency.record(value.as_nanos() as u64).ok();
+    }
+
+    pub fn record_gap(&mut self, value: Duration) {
+        self.gaps += 1;
+        self.largest_gap = self.largest_gap.max(value);
+    }
+
+    pub fn latency(&self) -> LatencyReport {
+        LatencyReport {
+            p50: self.latency.value_at_percentile(50.0),
+            p95: self.latency.value_at_percentile(95.0),
+            p99: self.latency.value_at_percentile(99.0),
+        }
+    }
+}
+
+impl Default for TimingStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct LatencyReport {
-    p50: u64,
-    p95: u64,
-    p99: u64,
+    pub p50: u64,
+    pub p95: u64,
+    pub p99: u64,
 }
 
-#[allow(dead_code)]
 pub struct StatsSummary {
-    add_order: LatencyReport,
-    cancel_order: LatencyReport,
-    match_order: LatencyReport,
+    pub add_order: LatencyReport,
+    pub modify_order: LatencyReport,
+    pub cancel_order: LatencyReport,
+    pub match_order: LatencyReport,
 }
 
 impl LatencyStats {
     pub fn new() -> LatencyStats {
         LatencyStats {
             add_order: Histogram::new(3).unwrap(),
+            modify_order: Histogram::new(3).unwrap(),
             cancel_order: Histogram::new(3).unwrap(),
             match_order: Histogram::new(3).unwrap(),
         }
@@ -34,6 +172,22 @@ impl LatencyStats {
     pub fn record_add(&mut self, value: Duration) {
         self.add_order.record(value.as_nanos() as u64).ok();
     }
+    pub fn record_modify(&mut self, value: Duration) {
+        self.modify_order.record(value.as_nanos() as u64).ok();
+    }
+    /// Records an event in the histogram for its kind. Parked and rejected
+    /// events never reached matchbook, so they aren't timed.
+    pub fn record_outcome(&mut self, event: &EventOutcome<'_>) {
+        match event.outcome {
+            Outcome::Added | Outcome::Replaced => self.record_add(event.elapsed),
+            Outcome::Modified | Outcome::Repriced => self.record_modify(event.elapsed),
+            Outcome::Deleted => self.record_cancel(event.elapsed),
+            Outcome::Parked | Outcome::Rejected => {}
+        }
+        if let Some(matching) = event.matching {
+            self.record_match(matching);
+        }
+    }
     pub fn record_cancel(&mut self, value: Duration) {
         self.cancel_order.record(value.as_nanos() as u64).ok();
     }
@@ -47,6 +201,11 @@ impl LatencyStats {
             p95: self.add_order.value_at_percentile(95.0),
             p99: self.add_order.value_at_percentile(99.0),
         };
+        let modify_order = LatencyReport {
+            p50: self.modify_order.value_at_percentile(50.0),
+            p95: self.modify_order.value_at_percentile(95.0),
+            p99: self.modify_order.value_at_percentile(99.0),
+        };
         let cancel_order = LatencyReport {
             p50: self.cancel_order.value_at_percentile(50.0),
             p95: self.cancel_order.value_at_percentile(95.0),
@@ -59,6 +218,7 @@ impl LatencyStats {
         };
         StatsSummary {
             add_order,
+            modify_order,
             cancel_order,
             match_order,
         }
@@ -0,0 +1,146 @@
+use crate::book::BookSide;
+use crate::decimal::Decimal;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+
+fn side<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BookSide, D::Error> {
+    match String::deserialize(deserializer)?.as_str() {
+        "buy" => Ok(BookSide::Bid),
+        "sell" => Ok(BookSide::Ask),
+        other => Err(D::Error::custom(format!("unknown side {:?}", other))),
+    }
+}
+
+/// An order accepted by the matching engine but not yet resting on the book.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Received {
+    pub product_id: String,
+    pub sequence: u64,
+    pub order_id: String,
+    #[serde(deserialize_with = "side")]
+    pub side: BookSide,
+    #[serde(default)]
+    pub price: Option<Decimal>,
+    #[serde(default)]
+    pub size: Option<Decimal>,
+    pub time: String,
+}
+
+/// The unfilled remainder of an order is now resting on the book.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Open {
+    pub product_id: String,
+    pub sequence: u64,
+    pub order_id: String,
+    #[serde(deserialize_with = "side")]
+    pub side: BookSide,
+    pub price: Decimal,
+    pub remaining_size: Decimal,
+    pub time: String,
+}
+
+/// The order is no longer on the book, whether it was filled or cancelled.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Done {
+    pub product_id: String,
+    pub sequence: u64,
+    pub order_id: String,
+    #[serde(deserialize_with = "side")]
+    pub side: BookSide,
+    pub reason: String,
+    #[serde(default)]
+    pub price: Option<Decimal>,
+    #[serde(default)]
+    pub remaining_size: Option<Decimal>,
+    pub time: String,
+}
+
+/// A trade between a resting maker order and an incoming taker order.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Match {
+    pub product_id: String,
+    pub sequence: u64,
+    pub trade_id: u64,
+    pub maker_order_id: String,
+    pub taker_order_id: String,
+    /// The maker order's side
+    #[serde(deserialize_with = "side")]
+    pub side: BookSide,
+    pub price: Decimal,
+    pub size: Decimal,
+    pub time: String,
+}
+
+/// A resting order changed size, or price for some order types.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Change {
+    pub product_id: String,
+    pub sequence: u64,
+    pub order_id: String,
+    #[serde(deserialize_with = "side")]
+    pub side: BookSide,
+    #[serde(default)]
+    pub price: Option<Decimal>,
+    #[serde(default)]
+    pub new_price: Option<Decimal>,
+    #[serde(default)]
+    pub new_size: Option<Decimal>,
+    #[serde(default)]
+    pub old_size: Option<Decimal>,
+    pub time: String,
+}
+
+/// A level 3 REST book (`price`, `size`, `order_id` rows) saved at the start
+/// of a capture, tagged with its product.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Snapshot {
+    pub product_id: String,
+    pub sequence: u64,
+    pub bids: Vec<(Decimal, Decimal, String)>,
+    pub asks: Vec<(Decimal, Decimal, String)>,
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    #[serde(rename = "type")]
+    message_type: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum CoinbaseMessage {
+    Snapshot(Snapshot),
+    Received(Received),
+    Open(Open),
+    Done(Done),
+    Match(Match),
+    Change(Change),
+    /// Subscription acks, heartbeats, stop order activations and the like
+    Other(String),
+}
+
+impl CoinbaseMessage {
+    pub fn parse(text: &str) -> Result<CoinbaseMessage, serde_json::Error> {
+        let envelope: Envelope = serde_json::from_str(text)?;
+        Ok(match envelope.message_type.as_str() {
+            "snapshot" => CoinbaseMessage::Snapshot(serde_json::from_str(text)?),
+            "received" => CoinbaseMessage::Received(serde_json::from_str(text)?),
+            "open" => CoinbaseMessage::Open(serde_json::from_str(text)?),
+            "done" => CoinbaseMessage::Done(serde_json::from_str(text)?),
+            "match" => CoinbaseMessage::Match(serde_json::from_str(text)?),
+            "change" => CoinbaseMessage::Change(serde_json::from_str(text)?),
+            _ => CoinbaseMessage::Other(envelope.message_type),
+        })
+    }
+
+    pub fn product_id(&self) -> Option<&str> {
+        match self {
+            CoinbaseMessage::Snapshot(message) => Some(&message.product_id),
+            CoinbaseMessage::Received(message) => Some(&message.product_id),
+            CoinbaseMessage::Open(message) => Some(&message.product_id),
+            CoinbaseMessage::Done(message) => Some(&message.product_id),
+            CoinbaseMessage::Match(message) => Some(&message.product_id),
+            CoinbaseMessage::Change(message) => Some(&message.product_id),
+            CoinbaseMessage::Other(_) => None,
+        }
+    }
+}
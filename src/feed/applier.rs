@@ -0,0 +1,580 @@
+use crate::FeedAdapter;
+use crate::book::{Book, BookMode, BookSet, BookSide, RestingOrder};
+use crate::decimal::{Decimal, DecimalError};
+use crate::event::{BookEvent, OrderUpdate};
+use crate::instrument::InstrumentSpec;
+use matchbook::{Order, OrderError, OrderId, OrderType, Price, Quantity, Side};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParseError {
+    #[error("Invalid channel from feed")]
+    InvalidChannel,
+    #[error("Invalid message type from feed")]
+    InvalidType,
+    #[error("Response is empty")]
+    Empty,
+    #[error("Data block has no symbol")]
+    MissingSymbol,
+    #[error("Unknown symbol: {0}")]
+    UnknownSymbol(String),
+    #[error("Update for {0} arrived before its snapshot")]
+    UpdateBeforeSnapshot(String),
+    #[error("Invalid price {raw} for order {order_id}: {reason}")]
+    InvalidPrice {
+        order_id: String,
+        raw: Decimal,
+        reason: DecimalError,
+    },
+    #[error("Invalid quantity {raw} for order {order_id}: {reason}")]
+    InvalidQuantity {
+        order_id: String,
+        raw: Decimal,
+        reason: DecimalError,
+    },
+    #[error("Failed to write audit log: {0}")]
+    AuditLog(#[from] std::io::Error),
+    #[error("Order Error: {0:?}")]
+    OrderError(#[from] OrderError),
+}
+
+/// How [`apply_events`] handles an order event it can't apply.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ApplyPolicy {
+    /// Stop at the first bad event and fail the batch
+    #[default]
+    Strict,
+    /// Apply every event that can be applied and report the rest
+    Lenient,
+    /// Lenient, and write every anomaly and error to the book set's audit log
+    Audit,
+}
+
+/// Events that applied but look wrong for a consistent feed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Anomaly {
+    /// An add for an order id already in the book
+    DuplicateAdd,
+    ModifyUnknownOrder,
+    ModifyToZero,
+    DeleteUnknownOrder,
+    /// An insert that would have crossed the book, parked in mirror mode
+    CrossingInsert,
+    /// A change to an order matchbook had already filled, in matching mode,
+    /// applied to the index alone
+    MatchConsumed,
+    /// A fill larger than the size left on its maker order, so the tracked
+    /// size had drifted from the venue's
+    Overfill,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnomalyRecord {
+    pub symbol: String,
+    pub order_id: String,
+    pub anomaly: Anomaly,
+}
+
+/// A book whose checksum didn't match the venue's, so it has drifted and
+/// should be resubscribed.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Checksum mismatch for {symbol}: expected {expected}, computed {actual}")]
+pub struct ChecksumMismatch {
+    pub symbol: String,
+    pub expected: u32,
+    pub actual: u32,
+}
+
+/// An event skipped under a lenient policy.
+#[derive(Debug)]
+pub struct EventError {
+    pub symbol: String,
+    pub order_id: String,
+    pub error: ParseError,
+}
+
+/// What a batch of events did to the books.
+#[derive(Debug, Default)]
+pub struct ApplyReport {
+    pub applied: usize,
+    pub errors: Vec<EventError>,
+    pub anomalies: Vec<AnomalyRecord>,
+    /// Books that failed their checksum. Their later events in the batch
+    /// are dropped, other symbols' still apply.
+    pub mismatches: Vec<ChecksumMismatch>,
+}
+
+impl ApplyReport {
+    fn mismatched(&self, symbol: &str) -> bool {
+        self.mismatches
+            .iter()
+            .any(|mismatch| mismatch.symbol == symbol)
+    }
+}
+
+/// What applying a single order event did to the book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Added,
+    /// An add for an order id already in the book, applied as cancel and re-add
+    Replaced,
+    Modified,
+    /// A modify that moved the order to a new price, applied as cancel and
+    /// re-add so it loses its queue position
+    Repriced,
+    Deleted,
+    /// Held in the index only because it would cross the book, see
+    /// [`BookMode::Mirror`]
+    Parked,
+    /// The event wasn't applied, e.g. the orderbook refused it. The strict
+    /// policy then fails the batch with its error, the lenient ones skip it
+    /// and report the error
+    Rejected,
+}
+
+/// One applied order event and how long matchbook took to apply it.
+#[derive(Debug, Clone, Copy)]
+pub struct EventOutcome<'a> {
+    pub symbol: &'a str,
+    pub order_id: &'a str,
+    pub outcome: Outcome,
+    /// Time spent in the orderbook's add, modify and cancel calls for the
+    /// event, excluding value scaling and index upkeep
+    pub elapsed: Duration,
+    /// Time spent in matchbook's matching after the event, in matching mode
+    pub matching: Option<Duration>,
+}
+
+/// Applies a batch of normalized events, typically one venue message, to
+/// `books`. The whole batch is rejected up front if any event names a symbol
+/// that isn't in `books`, or touches a book that hasn't loaded a snapshot.
+///
+/// Bad events are handled according to the book set's [`ApplyPolicy`]. Under
+/// the strict policy a price or quantity that doesn't convert exactly for its
+/// pair also rejects the batch up front, and any other failure stops it
+/// partway. The lenient policies skip just the failing events and return
+/// them in the report.
+///
+/// Checksum events are verified with `adapter` under every policy. A
+/// mismatch is recorded in the report and the rest of that symbol's events
+/// are dropped, since its book has drifted and should be resubscribed.
+pub fn apply_events<A, F>(
+    adapter: &A,
+    books: &mut BookSet,
+    events: &[BookEvent],
+    mut on_event: F,
+) -> Result<ApplyReport, ParseError>
+where
+    A: FeedAdapter + ?Sized,
+    F: FnMut(EventOutcome<'_>),
+{
+    let mode = books.mode();
+    let policy = books.policy();
+    let mut report = ApplyReport::default();
+
+    let mut snapshots = HashSet::new();
+    for event in events {
+        let Some(symbol) = event.symbol() else {
+            continue;
+        };
+        let spec = books
+            .spec(symbol)
+            .ok_or_else(|| ParseError::UnknownSymbol(symbol.to_string()))?;
+        let order = match event {
+            BookEvent::SnapshotStart { .. } => {
+                snapshots.insert(symbol);
+                continue;
+            }
+            BookEvent::AddOrder { order, .. } | BookEvent::ModifyOrder { order, .. } => Some(order),
+            BookEvent::DeleteOrder { .. } => None,
+            _ => continue,
+        };
+        if !snapshots.contains(symbol) && !books.is_synced(symbol) {
+            return Err(ParseError::UpdateBeforeSnapshot(symbol.to_string()));
+        }
+        // Deletes only need the order id, so their values are never looked at
+        if policy == ApplyPolicy::Strict
+            && let Some(order) = order
+        {
+            scale_price(order, &spec)?;
+            scale_qty(order, &spec)?;
+        }
+    }
+
+    for event in events {
+        let (symbol, order_id) = match event {
+            BookEvent::Heartbeat | BookEvent::Gap { .. } => continue,
+            BookEvent::SnapshotStart { symbol } => {
+                book_mut(books, symbol)?.clear();
+                continue;
+            }
+            BookEvent::SnapshotEnd { symbol } => {
+                book_mut(books, symbol)?.mark_synced();
+                continue;
+            }
+            BookEvent::Checksum { .. } if mode == BookMode::Matching => continue,
+            BookEvent::Checksum { symbol, .. } if report.mismatched(symbol) => continue,
+            BookEvent::Checksum { symbol, expected } => {
+                let book = book_mut(books, symbol)?;
+                if let Some(actual) = adapter.checksum(book)
+                    && actual != *expected
+                {
+                    report.mismatches.push(ChecksumMismatch {
+                        symbol: symbol.clone(),
+                        expected: *expected,
+                        actual,
+                    });
+                }
+                continue;
+            }
+            BookEvent::AddOrder { symbol, order } | BookEvent::ModifyOrder { symbol, order } => {
+                (symbol, &order.order_id)
+            }
+            BookEvent::DeleteOrder { symbol, order_id } => (symbol, order_id),
+        };
+        if report.mismatched(symbol) {
+            continue;
+        }
+
+        let book = book_mut(books, symbol)?;
+        if let Some(anomaly) = detect_anomaly(book, event) {
+            report.anomalies.push(AnomalyRecord {
+                symbol: symbol.clone(),
+                order_id: order_id.clone(),
+                anomaly,
+            });
+        }
+
+        // Parked orders can only stop crossing when the opposite best moves
+        let before = (mode == BookMode::Mirror && !book.parked().is_empty()).then(|| {
+            (
+                best_live(book, BookSide::Bid),
+                best_live(book, BookSide::Ask),
+            )
+        });
+        let mut elapsed = Duration::ZERO;
+        let mut consumed = false;
+        let result = match event {
+            BookEvent::AddOrder { order, .. } => {
+                add_order(book, order, mode, &mut elapsed, &mut consumed)
+            }
+            BookEvent::ModifyOrder { order, .. } => {
+                modify_order(book, order, mode, &mut elapsed, &mut consumed)
+            }
+            _ => delete_order(book, order_id, mode, &mut elapsed, &mut consumed),
+        };
+        let result = match before {
+            Some(before) => {
+                result.and_then(|outcome| release_parked(book, before).map(|()| outcome))
+            }
+            None => result,
+        };
+
+        let outcome = *result.as_ref().unwrap_or(&Outcome::Rejected);
+        let matching = match (mode, outcome) {
+            (BookMode::Matching, Outcome::Added | Outcome::Replaced | Outcome::Repriced) => {
+                let started = Instant::now();
+                let _ = book.orderbook.match_orders();
+                Some(started.elapsed())
+            }
+            _ => None,
+        };
+        if outcome == Outcome::Parked {
+            report.anomalies.push(AnomalyRecord {
+                symbol: symbol.clone(),
+                order_id: order_id.clone(),
+                anomaly: Anomaly::CrossingInsert,
+            });
+        }
+        if consumed {
+            report.anomalies.push(AnomalyRecord {
+                symbol: symbol.clone(),
+                order_id: order_id.clone(),
+                anomaly: Anomaly::MatchConsumed,
+            });
+        }
+
+        on_event(EventOutcome {
+            symbol,
+            order_id,
+            outcome,
+            elapsed,
+            matching,
+        });
+
+        match result {
+            Ok(_) => report.applied += 1,
+            Err(error) if policy == ApplyPolicy::Strict => return Err(error),
+            Err(error) => report.errors.push(EventError {
+                symbol: symbol.clone(),
+                order_id: order_id.clone(),
+                error,
+            }),
+        }
+    }
+
+    audit(books, policy, &report)?;
+    Ok(report)
+}
+
+fn book_mut<'a>(books: &'a mut BookSet, symbol: &str) -> Result<&'a mut Book, ParseError> {
+    books
+        .book_mut(symbol)
+        .ok_or_else(|| ParseError::UnknownSymbol(symbol.to_string()))
+}
+
+fn audit(books: &mut BookSet, policy: ApplyPolicy, report: &ApplyReport) -> Result<(), ParseError> {
+    if policy == ApplyPolicy::Audit
+        && let Some(log) = books.audit_log_mut()
+    {
+        log.record_report(report)?;
+    }
+    Ok(())
+}
+
+fn detect_anomaly(book: &Book, event: &BookEvent) -> Option<Anomaly> {
+    match event {
+        BookEvent::AddOrder { order, .. } if book.orders.get(&order.order_id).is_some() => {
+            Some(Anomaly::DuplicateAdd)
+        }
+        BookEvent::ModifyOrder { order, .. } if book.orders.get(&order.order_id).is_none() => {
+            Some(Anomaly::ModifyUnknownOrder)
+        }
+        BookEvent::ModifyOrder { order, .. } if order.qty.is_zero() => Some(Anomaly::ModifyToZero),
+        BookEvent::DeleteOrder { order_id, .. } if book.orders.get(order_id).is_none() => {
+            Some(Anomaly::DeleteUnknownOrder)
+        }
+        _ => None,
+    }
+}
+
+fn add_order(
+    book: &mut Book,
+    update: &OrderUpdate,
+    mode: BookMode,
+    elapsed: &mut Duration,
+    consumed: &mut bool,
+) -> Result<Outcome, ParseError> {
+    let price = scale_price(update, &book.spec)?;
+    let qty = scale_qty(update, &book.spec)?;
+    let replaced = book.orders.get(&update.order_id).is_some();
+    if replaced {
+        // Order exists - this is a replace/update
+        // Delete old and add new
+        withdraw(book, &update.order_id, mode, elapsed, consumed)?;
+    }
+    let parked = insert(book, update, price, qty, mode, elapsed)?;
+    Ok(match (parked, replaced) {
+        (true, _) => Outcome::Parked,
+        (false, true) => Outcome::Replaced,
+        (false, false) => Outcome::Added,
+    })
+}
+
+fn modify_order(
+    book: &mut Book,
+    update: &OrderUpdate,
+    mode: BookMode,
+    elapsed: &mut Duration,
+    consumed: &mut bool,
+) -> Result<Outcome, ParseError> {
+    let price = scale_price(update, &book.spec)?;
+    let qty = scale_qty(update, &book.spec)?;
+    if qty == 0 {
+        // Nothing left resting, so don't leave an empty order in the book
+        withdraw(book, &update.order_id, mode, elapsed, consumed)?;
+        Ok(Outcome::Deleted)
+    } else if book
+        .orders
+        .get(&update.order_id)
+        .is_some_and(|resting| resting.price != price)
+    {
+        // Venues keep the order id across a price change, but the order goes
+        // to the back of the queue at its new level
+        withdraw(book, &update.order_id, mode, elapsed, consumed)?;
+        let parked = insert(book, update, price, qty, mode, elapsed)?;
+        Ok(if parked {
+            Outcome::Parked
+        } else {
+            Outcome::Repriced
+        })
+    } else {
+        let known = book.orders.set_qty(&update.order_id, qty);
+        if !book.is_parked(&update.order_id) {
+            let id = to_order_id(&update.order_id);
+            let result = timed(elapsed, || book.orderbook.modify_order(id, Quantity(qty)));
+            match result {
+                Err(_) if known && mode == BookMode::Matching => *consumed = true,
+                result => result?,
+            }
+        }
+        Ok(Outcome::Modified)
+    }
+}
+
+fn delete_order(
+    book: &mut Book,
+    order_id: &str,
+    mode: BookMode,
+    elapsed: &mut Duration,
+    consumed: &mut bool,
+) -> Result<Outcome, ParseError> {
+    withdraw(book, order_id, mode, elapsed, consumed)?;
+    Ok(Outcome::Deleted)
+}
+
+/// Adds an order to the index and to the orderbook, unless mirror mode has
+/// to park it. Returns whether it was parked.
+fn insert(
+    book: &mut Book,
+    update: &OrderUpdate,
+    price: u64,
+    qty: u64,
+    mode: BookMode,
+    elapsed: &mut Duration,
+) -> Result<bool, ParseError> {
+    let parked = mode == BookMode::Mirror && crosses(book, update.side, price);
+    if parked {
+        book.park(update.order_id.clone(), update.side, price);
+    } else {
+        let order = to_order(update, price, qty);
+        timed(elapsed, || book.orderbook.add_order(order))?;
+    }
+    book.orders.insert(
+        update.order_id.clone(),
+        RestingOrder {
+            side: update.side,
+            price,
+            qty,
+        },
+    );
+    Ok(parked)
+}
+
+/// Removes an order from the index, and from the orderbook unless it was
+/// parked. In matching mode an indexed order the orderbook no longer holds
+/// was filled there, so it is marked `consumed` rather than failing.
+fn withdraw(
+    book: &mut Book,
+    order_id: &str,
+    mode: BookMode,
+    elapsed: &mut Duration,
+    consumed: &mut bool,
+) -> Result<(), ParseError> {
+    let known = book.orders.remove(order_id).is_some();
+    if !book.unpark(order_id) {
+        let id = to_order_id(order_id);
+        match timed(elapsed, || book.orderbook.cancel_order(id)) {
+            Err(_) if known && mode == BookMode::Matching => *consumed = true,
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+/// Runs one orderbook call, adding how long it took to `elapsed`.
+fn timed<T>(elapsed: &mut Duration, call: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = call();
+    *elapsed += started.elapsed();
+    result
+}
+
+/// The best price on `side` held in the orderbook, skipping levels where
+/// every order is parked.
+fn best_live(book: &Book, side: BookSide) -> Option<u64> {
+    let parked = book.parked();
+    let live = |(price, ids): &(u64, &[String])| parked.count_at(side, *price) < ids.len();
+    match side {
+        BookSide::Bid => book.orders.bids().find(live),
+        BookSide::Ask => book.orders.asks().find(live),
+    }
+    .map(|(price, _)| price)
+}
+
+/// Whether an order at `price` would trade against the orders matchbook holds
+/// on the other side. Parked orders aren't in matchbook, so they don't count.
+fn crosses(book: &Book, side: BookSide, price: u64) -> bool {
+    match side {
+        BookSide::Bid => best_live(book, BookSide::Ask).is_some_and(|ask| price >= ask),
+        BookSide::Ask => best_live(book, BookSide::Bid).is_some_and(|bid| price <= bid),
+    }
+}
+
+/// Hands parked orders to matchbook once the opposite best price has moved
+/// away from them. `before` is the best live bid and ask before the event.
+fn release_parked(book: &mut Book, before: (Option<u64>, Option<u64>)) -> Result<(), ParseError> {
+    let (bid, ask) = before;
+    let best_ask = best_live(book, BookSide::Ask);
+    if best_ask != ask {
+        let released = book.parked().releasable(BookSide::Bid, best_ask);
+        release(book, released)?;
+    }
+    // Released bids can raise the best bid too
+    let best_bid = best_live(book, BookSide::Bid);
+    if best_bid != bid {
+        let released = book.parked().releasable(BookSide::Ask, best_bid);
+        release(book, released)?;
+    }
+    Ok(())
+}
+
+fn release(book: &mut Book, order_ids: Vec<String>) -> Result<(), ParseError> {
+    for order_id in order_ids {
+        book.unpark(&order_id);
+        let Some(order) = book.orders.get(&order_id).cloned() else {
+            continue;
+        };
+        book.orderbook.add_order(Order::new(
+            to_order_id(&order_id),
+            OrderType::GoodTillCancelled,
+            to_side(order.side),
+            Price::new(order.price),
+            Quantity(order.qty),
+        ))?;
+    }
+    Ok(())
+}
+
+fn scale_price(update: &OrderUpdate, spec: &InstrumentSpec) -> Result<u64, ParseError> {
+    spec.scale_price(&update.price)
+        .map_err(|reason| ParseError::InvalidPrice {
+            order_id: update.order_id.clone(),
+            raw: update.price,
+            reason,
+        })
+}
+
+fn scale_qty(update: &OrderUpdate, spec: &InstrumentSpec) -> Result<u64, ParseError> {
+    spec.scale_qty(&update.qty)
+        .map_err(|reason| ParseError::InvalidQuantity {
+            order_id: update.order_id.clone(),
+            raw: update.qty,
+            reason,
+        })
+}
+
+pub fn to_order_id(venue_id: &str) -> OrderId {
+    OrderId::new(venue_id.to_string())
+}
+
+pub fn to_side(side: BookSide) -> Side {
+    match side {
+        BookSide::Bid => Side::Buy,
+        BookSide::Ask => Side::Sell,
+    }
+}
+
+/// A good-till-cancelled matchbook order at already scaled `price` and `qty`.
+pub fn to_order(update: &OrderUpdate, price: u64, qty: u64) -> Order {
+    Order::new(
+        to_order_id(&update.order_id),
+        OrderType::GoodTillCancelled,
+        to_side(update.side),
+        Price::new(price),
+        Quantity(qty),
+    )
+}
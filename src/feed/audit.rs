@@ -0,0 +1,76 @@
+use crate::applier::{Anomaly, ApplyReport};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// One line of the audit log.
+#[derive(Debug, Serialize)]
+pub struct AuditRecord<'a> {
+    pub symbol: &'a str,
+    pub order_id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anomaly: Option<Anomaly>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Appends every anomaly and rejected event to a JSON lines file for review
+/// after a run.
+pub struct AuditLog {
+    writer: BufWriter<File>,
+    records: u64,
+}
+
+impl AuditLog {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<AuditLog> {
+        Ok(AuditLog {
+            writer: BufWriter::new(File::create(path)?),
+            records: 0,
+        })
+    }
+
+    pub fn record(&mut self, record: &AuditRecord<'_>) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        self.records += 1;
+        Ok(())
+    }
+
+    pub fn record_report(&mut self, report: &ApplyReport) -> io::Result<()> {
+        for anomaly in &report.anomalies {
+            self.record(&AuditRecord {
+                symbol: &anomaly.symbol,
+                order_id: &anomaly.order_id,
+                anomaly: Some(anomaly.anomaly),
+                error: None,
+            })?;
+        }
+        for mismatch in &report.mismatches {
+            self.record(&AuditRecord {
+                symbol: &mismatch.symbol,
+                order_id: "",
+                anomaly: None,
+                error: Some(mismatch.to_string()),
+            })?;
+        }
+        for error in &report.errors {
+            self.record(&AuditRecord {
+                symbol: &error.symbol,
+                order_id: &error.order_id,
+                anomaly: None,
+                error: Some(error.error.to_string()),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Number of lines written so far.
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,395 @@
+use crate::applier::ApplyPolicy;
+use crate::audit::AuditLog;
+use crate::instrument::InstrumentSpec;
+use matchbook::Orderbook;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BookSide {
+    Bid,
+    Ask,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestingOrder {
+    pub side: BookSide,
+    pub price: u64,
+    pub qty: u64,
+}
+
+/// Every resting order in queue priority, kept next to the matchbook book
+/// because matchbook doesn't expose the orders it holds.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OrderIndex {
+    orders: HashMap<String, RestingOrder>,
+    bids: BTreeMap<u64, Vec<String>>,
+    asks: BTreeMap<u64, Vec<String>>,
+}
+
+impl OrderIndex {
+    pub fn new() -> OrderIndex {
+        OrderIndex::default()
+    }
+
+    /// Adds an order to the back of its level, replacing any order with the same id.
+    pub fn insert(&mut self, id: String, order: RestingOrder) {
+        self.remove(&id);
+        self.levels_mut(order.side)
+            .entry(order.price)
+            .or_default()
+            .push(id.clone());
+        self.orders.insert(id, order);
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<RestingOrder> {
+        let order = self.orders.remove(id)?;
+        let levels = self.levels_mut(order.side);
+        if let Some(queue) = levels.get_mut(&order.price) {
+            queue.retain(|queued| queued != id);
+            if queue.is_empty() {
+                levels.remove(&order.price);
+            }
+        }
+        Some(order)
+    }
+
+    /// Changes an order's quantity in place, keeping its queue position.
+    pub fn set_qty(&mut self, id: &str, qty: u64) -> bool {
+        match self.orders.get_mut(id) {
+            Some(order) => {
+                order.qty = qty;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&RestingOrder> {
+        self.orders.get(id)
+    }
+
+    /// Bid levels from the best (highest) price down, each in queue order.
+    pub fn bids(&self) -> impl Iterator<Item = (u64, &[String])> {
+        self.bids
+            .iter()
+            .rev()
+            .map(|(price, ids)| (*price, ids.as_slice()))
+    }
+
+    /// Ask levels from the best (lowest) price up, each in queue order.
+    pub fn asks(&self) -> impl Iterator<Item = (u64, &[String])> {
+        self.asks
+            .iter()
+            .map(|(price, ids)| (*price, ids.as_slice()))
+    }
+
+    pub fn clear(&mut self) {
+        self.orders.clear();
+        self.bids.clear();
+        self.asks.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    fn levels_mut(&mut self, side: BookSide) -> &mut BTreeMap<u64, Vec<String>> {
+        match side {
+            BookSide::Bid => &mut self.bids,
+            BookSide::Ask => &mut self.asks,
+        }
+    }
+}
+
+/// Orders in the index but held back from the orderbook because they would
+/// cross it, by side and price and in arrival order within a price.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParkedOrders {
+    orders: HashMap<String, (BookSide, u64)>,
+    bids: BTreeMap<u64, Vec<String>>,
+    asks: BTreeMap<u64, Vec<String>>,
+}
+
+impl ParkedOrders {
+    pub fn contains(&self, order_id: &str) -> bool {
+        self.orders.contains_key(order_id)
+    }
+
+    pub fn insert(&mut self, order_id: String, side: BookSide, price: u64) {
+        self.remove(&order_id);
+        self.levels_mut(side)
+            .entry(price)
+            .or_default()
+            .push(order_id.clone());
+        self.orders.insert(order_id, (side, price));
+    }
+
+    /// Returns whether the order was parked.
+    pub fn remove(&mut self, order_id: &str) -> bool {
+        let Some((side, price)) = self.orders.remove(order_id) else {
+            return false;
+        };
+        let levels = self.levels_mut(side);
+        if let Some(queue) = levels.get_mut(&price) {
+            queue.retain(|parked| parked != order_id);
+            if queue.is_empty() {
+                levels.remove(&price);
+            }
+        }
+        true
+    }
+
+    /// How many orders are parked at one price.
+    pub fn count_at(&self, side: BookSide, price: u64) -> usize {
+        self.levels(side).get(&price).map_or(0, Vec::len)
+    }
+
+    /// Orders on `side` that no longer cross a book whose best opposite
+    /// price held in the orderbook is `opposite`, in arrival order per price.
+    pub fn releasable(&self, side: BookSide, opposite: Option<u64>) -> Vec<String> {
+        let range = match (side, opposite) {
+            (_, None) => (Bound::Unbounded, Bound::Unbounded),
+            (BookSide::Bid, Some(ask)) => (Bound::Unbounded, Bound::Excluded(ask)),
+            (BookSide::Ask, Some(bid)) => (Bound::Excluded(bid), Bound::Unbounded),
+        };
+        self.levels(side)
+            .range(range)
+            .flat_map(|(_, ids)| ids)
+            .cloned()
+            .collect()
+    }
+
+    /// Parked bids by price then parked asks by price.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.bids
+            .values()
+            .chain(self.asks.values())
+            .flatten()
+            .map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    fn levels(&self, side: BookSide) -> &BTreeMap<u64, Vec<String>> {
+        match side {
+            BookSide::Bid => &self.bids,
+            BookSide::Ask => &self.asks,
+        }
+    }
+
+    fn levels_mut(&mut self, side: BookSide) -> &mut BTreeMap<u64, Vec<String>> {
+        match side {
+            BookSide::Bid => &mut self.bids,
+            BookSide::Ask => &mut self.asks,
+        }
+    }
+}
+
+/// Whether matchbook is allowed to match the orders it is fed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BookMode {
+    /// Keep an exact copy of the venue's book. An insert that would cross
+    /// the book is parked in the index, out of matchbook's reach, until the
+    /// opposite side moves away.
+    #[default]
+    Mirror,
+    /// Feed every order to matchbook and run its matching after each insert.
+    /// Fills make the book diverge from the venue's, so it is no longer a
+    /// mirror: checksums aren't verified and the index keeps following the
+    /// venue. A change to an order matchbook already filled is applied to the
+    /// index alone and reported as
+    /// [`Anomaly::MatchConsumed`](crate::applier::Anomaly::MatchConsumed).
+    Matching,
+}
+
+/// A mirrored matchbook [`Orderbook`] and the index of the orders in it.
+pub struct Book {
+    pub orderbook: Orderbook,
+    pub orders: OrderIndex,
+    pub spec: InstrumentSpec,
+    synced: bool,
+    parked: ParkedOrders,
+}
+
+impl Book {
+    pub fn new(spec: InstrumentSpec) -> Book {
+        Book {
+            orderbook: Orderbook::new(),
+            orders: OrderIndex::new(),
+            spec,
+            synced: false,
+            parked: ParkedOrders::default(),
+        }
+    }
+
+    /// Empties the book, keeping its instrument spec. It stays unsynced
+    /// until the next snapshot is loaded.
+    pub fn clear(&mut self) {
+        *self = Book::new(self.spec);
+    }
+
+    /// Whether a snapshot has been loaded since the book was last cleared.
+    pub fn is_synced(&self) -> bool {
+        self.synced
+    }
+
+    pub fn mark_synced(&mut self) {
+        self.synced = true;
+    }
+
+    pub fn is_parked(&self, order_id: &str) -> bool {
+        self.parked.contains(order_id)
+    }
+
+    pub fn parked(&self) -> &ParkedOrders {
+        &self.parked
+    }
+
+    pub fn park(&mut self, order_id: impl Into<String>, side: BookSide, price: u64) {
+        self.parked.insert(order_id.into(), side, price);
+    }
+
+    /// Returns whether the order was parked.
+    pub fn unpark(&mut self, order_id: &str) -> bool {
+        self.parked.remove(order_id)
+    }
+}
+
+/// One mirrored [`Book`] per subscribed symbol, and how errors applying
+/// data to them are handled.
+#[derive(Default)]
+pub struct BookSet {
+    books: HashMap<String, Book>,
+    mode: BookMode,
+    policy: ApplyPolicy,
+    audit: Option<AuditLog>,
+}
+
+impl BookSet {
+    pub fn new() -> BookSet {
+        BookSet::default()
+    }
+
+    pub fn with_symbols<I, S>(symbols: I) -> BookSet
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut books = BookSet::new();
+        for symbol in symbols {
+            books.register(symbol);
+        }
+        books
+    }
+
+    pub fn mode(&self) -> BookMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: BookMode) {
+        self.mode = mode;
+    }
+
+    pub fn policy(&self) -> ApplyPolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: ApplyPolicy) {
+        self.policy = policy;
+    }
+
+    /// Switches to [`ApplyPolicy::Audit`], writing anomalies to `log`.
+    pub fn audit_to(&mut self, log: AuditLog) {
+        self.policy = ApplyPolicy::Audit;
+        self.audit = Some(log);
+    }
+
+    pub fn audit_log(&self) -> Option<&AuditLog> {
+        self.audit.as_ref()
+    }
+
+    pub fn audit_log_mut(&mut self) -> Option<&mut AuditLog> {
+        self.audit.as_mut()
+    }
+
+    /// Starts mirroring `symbol` with an empty book, if it isn't already.
+    /// Pairs missing from the known instrument table use the default spec.
+    pub fn register(&mut self, symbol: impl Into<String>) {
+        let symbol = symbol.into();
+        let spec = InstrumentSpec::for_symbol(&symbol).unwrap_or_default();
+        self.register_with_spec(symbol, spec);
+    }
+
+    pub fn register_with_spec(&mut self, symbol: impl Into<String>, spec: InstrumentSpec) {
+        self.books
+            .entry(symbol.into())
+            .or_insert_with(|| Book::new(spec));
+    }
+
+    pub fn is_synced(&self, symbol: &str) -> bool {
+        self.books.get(symbol).is_some_and(Book::is_synced)
+    }
+
+    pub fn spec(&self, symbol: &str) -> Option<InstrumentSpec> {
+        self.books.get(symbol).map(|book| book.spec)
+    }
+
+    pub fn remove(&mut self, symbol: &str) -> Option<Book> {
+        self.books.remove(symbol)
+    }
+
+    pub fn contains(&self, symbol: &str) -> bool {
+        self.books.contains_key(symbol)
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<&Orderbook> {
+        self.books.get(symbol).map(|book| &book.orderbook)
+    }
+
+    pub fn book(&self, symbol: &str) -> Option<&Book> {
+        self.books.get(symbol)
+    }
+
+    pub fn book_mut(&mut self, symbol: &str) -> Option<&mut Book> {
+        self.books.get_mut(symbol)
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = &str> {
+        self.books.keys().map(String::as_str)
+    }
+
+    /// Replaces the book for `symbol` with an empty one.
+    pub fn reset_symbol(&mut self, symbol: &str) {
+        if let Some(book) = self.books.get_mut(symbol) {
+            book.clear();
+        }
+    }
+
+    /// Replaces every book with an empty one, keeping the symbols registered.
+    pub fn reset(&mut self) {
+        for book in self.books.values_mut() {
+            book.clear();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.books.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.books.is_empty()
+    }
+}
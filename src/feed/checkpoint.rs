@@ -0,0 +1,204 @@
+use crate::applier::{ParseError, to_order_id, to_side};
+use crate::book::{Book, BookSet, BookSide, RestingOrder};
+use crate::instrument::InstrumentSpec;
+use matchbook::{Order, OrderType, Price, Quantity};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Every resting order of a set of mirrored books, as written into captures
+/// so a replay can start partway through.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BookCheckpoint {
+    /// Sorted by symbol
+    pub books: Vec<SymbolCheckpoint>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolCheckpoint {
+    pub symbol: String,
+    /// Precision the orders are scaled at, missing from checkpoints written
+    /// before it was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spec: Option<InstrumentSpec>,
+    pub synced: bool,
+    /// Bids best first then asks best first, each level in queue priority
+    pub orders: Vec<CheckpointOrder>,
+    /// Orders held back from matchbook in mirror mode, bids then asks by
+    /// price
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parked: Vec<String>,
+}
+
+/// A resting order at the pair's scaled precision.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointOrder {
+    pub order_id: String,
+    pub side: BookSide,
+    pub price: u64,
+    pub qty: u64,
+}
+
+impl Book {
+    pub fn checkpoint(&self, symbol: &str) -> SymbolCheckpoint {
+        let orders = self
+            .orders
+            .bids()
+            .chain(self.orders.asks())
+            .flat_map(|(_, ids)| ids)
+            .filter_map(|id| {
+                let order = self.orders.get(id)?;
+                Some(CheckpointOrder {
+                    order_id: id.clone(),
+                    side: order.side,
+                    price: order.price,
+                    qty: order.qty,
+                })
+            })
+            .collect();
+        SymbolCheckpoint {
+            symbol: symbol.to_string(),
+            spec: Some(self.spec),
+            synced: self.is_synced(),
+            orders,
+            parked: self.parked().iter().map(str::to_string).collect(),
+        }
+    }
+
+    /// Replaces the book with the checkpointed orders, adding them to a
+    /// fresh orderbook in queue priority.
+    pub fn restore(&mut self, checkpoint: &SymbolCheckpoint) -> Result<(), ParseError> {
+        self.clear();
+        let parked: HashSet<&str> = checkpoint.parked.iter().map(String::as_str).collect();
+        for order in &checkpoint.orders {
+            self.orders.insert(
+                order.order_id.clone(),
+                RestingOrder {
+                    side: order.side,
+                    price: order.price,
+                    qty: order.qty,
+                },
+            );
+            if parked.contains(order.order_id.as_str()) {
+                self.park(order.order_id.clone(), order.side, order.price);
+                continue;
+            }
+            self.orderbook.add_order(Order::new(
+                to_order_id(&order.order_id),
+                OrderType::GoodTillCancelled,
+                to_side(order.side),
+                Price::new(order.price),
+                Quantity(order.qty),
+            ))?;
+        }
+        if checkpoint.synced {
+            self.mark_synced();
+        }
+        Ok(())
+    }
+}
+
+impl BookSet {
+    pub fn checkpoint(&self) -> BookCheckpoint {
+        let mut symbols: Vec<&str> = self.symbols().collect();
+        symbols.sort_unstable();
+        let books = symbols
+            .into_iter()
+            .filter_map(|symbol| Some(self.book(symbol)?.checkpoint(symbol)))
+            .collect();
+        BookCheckpoint { books }
+    }
+
+    /// Loads every book in `checkpoint`, registering symbols subscribed
+    /// after the books were created at the precision they were taken at.
+    /// A symbol without a recorded spec must already be registered. If any
+    /// book fails to load, none are changed.
+    pub fn restore(&mut self, checkpoint: &BookCheckpoint) -> Result<(), ParseError> {
+        let mut restored = Vec::with_capacity(checkpoint.books.len());
+        for symbol in &checkpoint.books {
+            let spec = symbol
+                .spec
+                .or_else(|| self.spec(&symbol.symbol))
+                .ok_or_else(|| ParseError::UnknownSymbol(symbol.symbol.clone()))?;
+            let mut book = Book::new(spec);
+            book.restore(symbol)?;
+            restored.push((symbol.symbol.as_str(), book));
+        }
+        for (symbol, book) in restored {
+            self.register_with_spec(symbol, book.spec);
+            if let Some(slot) = self.book_mut(symbol) {
+                *slot = book;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How the orders of a book changed between two checkpoints.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderDiff {
+    pub added: Vec<CheckpointOrder>,
+    pub removed: Vec<CheckpointOrder>,
+    pub resized: Vec<ResizedOrder>,
+}
+
+/// An order still resting at the same price with a different quantity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResizedOrder {
+    pub order_id: String,
+    pub side: BookSide,
+    pub price: u64,
+    pub before: u64,
+    pub after: u64,
+}
+
+impl OrderDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.resized.is_empty()
+    }
+}
+
+impl SymbolCheckpoint {
+    /// The orders added, removed and resized between this checkpoint and
+    /// `later`, each in `later`'s queue priority, or this one's for removed
+    /// orders. An order that moved price lost its queue position, so it
+    /// counts as removed and added again.
+    pub fn diff(&self, later: &SymbolCheckpoint) -> OrderDiff {
+        let before: HashMap<&str, &CheckpointOrder> = self
+            .orders
+            .iter()
+            .map(|order| (order.order_id.as_str(), order))
+            .collect();
+        let after: HashMap<&str, &CheckpointOrder> = later
+            .orders
+            .iter()
+            .map(|order| (order.order_id.as_str(), order))
+            .collect();
+
+        let mut diff = OrderDiff::default();
+        for order in &later.orders {
+            match before.get(order.order_id.as_str()) {
+                Some(old) if old.side == order.side && old.price == order.price => {
+                    if old.qty != order.qty {
+                        diff.resized.push(ResizedOrder {
+                            order_id: order.order_id.clone(),
+                            side: order.side,
+                            price: order.price,
+                            before: old.qty,
+                            after: order.qty,
+                        });
+                    }
+                }
+                _ => diff.added.push(order.clone()),
+            }
+        }
+        for order in &self.orders {
+            let kept = after
+                .get(order.order_id.as_str())
+                .is_some_and(|new| new.side == order.side && new.price == order.price);
+            if !kept {
+                diff.removed.push(order.clone());
+            }
+        }
+        diff
+    }
+}
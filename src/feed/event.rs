@@ -0,0 +1,66 @@
+use crate::book::BookSide;
+use crate::decimal::Decimal;
+use chrono::{DateTime, Utc};
+
+/// A resting order as a venue reports it, before scaling to the pair's precision.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderUpdate {
+    pub order_id: String,
+    pub side: BookSide,
+    pub price: Decimal,
+    pub qty: Decimal,
+}
+
+/// A venue-independent change to a mirrored L3 book.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BookEvent {
+    /// The book for `symbol` is about to be replaced by a snapshot
+    SnapshotStart {
+        symbol: String,
+    },
+    /// Every order of the snapshot has been sent
+    SnapshotEnd {
+        symbol: String,
+    },
+    AddOrder {
+        symbol: String,
+        order: OrderUpdate,
+    },
+    /// A new quantity, and possibly a new price, for a resting order
+    ModifyOrder {
+        symbol: String,
+        order: OrderUpdate,
+    },
+    DeleteOrder {
+        symbol: String,
+        order_id: String,
+    },
+    /// The venue's checksum of the book as it should be at this point
+    Checksum {
+        symbol: String,
+        expected: u32,
+    },
+    /// No update for `symbol` between `since` and `at`, longer than the
+    /// adapter's gap threshold
+    Gap {
+        symbol: String,
+        since: DateTime<Utc>,
+        at: DateTime<Utc>,
+    },
+    Heartbeat,
+}
+
+impl BookEvent {
+    pub fn symbol(&self) -> Option<&str> {
+        match self {
+            BookEvent::SnapshotStart { symbol }
+            | BookEvent::SnapshotEnd { symbol }
+            | BookEvent::AddOrder { symbol, .. }
+            | BookEvent::ModifyOrder { symbol, .. }
+            | BookEvent::DeleteOrder { symbol, .. }
+            | BookEvent::Checksum { symbol, .. }
+            | BookEvent::Gap { symbol, .. } => Some(symbol),
+            BookEvent::Heartbeat => None,
+        }
+    }
+}
@@ -0,0 +1,29 @@
+pub mod applier;
+pub mod audit;
+pub mod book;
+pub mod checkpoint;
+pub mod event;
+pub mod timing;
+
+use crate::applier::ParseError;
+use crate::book::Book;
+use crate::event::BookEvent;
+
+/// Turns one venue's messages into [`BookEvent`]s for the shared applier.
+pub trait FeedAdapter {
+    type Message;
+
+    /// Appends the book events carried by `message` to `events`. Messages
+    /// that don't touch the book, like subscription acks, add nothing.
+    fn normalize(
+        &mut self,
+        message: &Self::Message,
+        events: &mut Vec<BookEvent>,
+    ) -> Result<(), ParseError>;
+
+    /// The venue's checksum of `book`, for venues that publish one alongside
+    /// [`BookEvent::Checksum`].
+    fn checksum(&self, _book: &Book) -> Option<u32> {
+        None
+    }
+}
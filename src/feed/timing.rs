@@ -0,0 +1,99 @@
+use crate::stats::TimingStats;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// How one exchange timestamp compares to the last one seen for its symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tick {
+    InOrder,
+    /// Earlier than an update already seen; the symbol's clock doesn't move back
+    OutOfOrder {
+        latest: DateTime<Utc>,
+    },
+    /// Later than the last update by more than the gap threshold
+    Gap {
+        since: DateTime<Utc>,
+    },
+}
+
+/// Follows each symbol's exchange timestamps to spot out-of-order updates
+/// and gaps in the feed, and how far behind the venue updates arrive.
+#[derive(Debug, Clone)]
+pub struct FeedClock {
+    gap_threshold: Duration,
+    latest: HashMap<String, DateTime<Utc>>,
+    stats: TimingStats,
+}
+
+impl FeedClock {
+    pub const DEFAULT_GAP_THRESHOLD: Duration = Duration::from_secs(5);
+
+    pub fn new(gap_threshold: Duration) -> FeedClock {
+        FeedClock {
+            gap_threshold,
+            latest: HashMap::new(),
+            stats: TimingStats::new(),
+        }
+    }
+
+    pub fn gap_threshold(&self) -> Duration {
+        self.gap_threshold
+    }
+
+    pub fn stats(&self) -> &TimingStats {
+        &self.stats
+    }
+
+    pub fn latest(&self, symbol: &str) -> Option<DateTime<Utc>> {
+        self.latest.get(symbol).copied()
+    }
+
+    /// Restarts `symbol`'s clock, e.g. at a snapshot, so the time spent
+    /// resubscribing isn't counted as a gap.
+    pub fn reset(&mut self, symbol: &str, at: Option<DateTime<Utc>>) {
+        match at {
+            Some(at) => self.latest.insert(symbol.to_string(), at),
+            None => self.latest.remove(symbol),
+        };
+    }
+
+    /// Records an update stamped `exchange` by the venue and received locally
+    /// at `received`.
+    ///
+    /// A local clock behind the venue's gives a negative latency, which is
+    /// recorded as zero.
+    pub fn observe(
+        &mut self,
+        symbol: &str,
+        exchange: DateTime<Utc>,
+        received: DateTime<Utc>,
+    ) -> Tick {
+        let latency = (received - exchange).to_std().unwrap_or_default();
+        self.stats.record_latency(latency);
+
+        let Some(latest) = self.latest(symbol) else {
+            self.latest.insert(symbol.to_string(), exchange);
+            return Tick::InOrder;
+        };
+        if exchange < latest {
+            self.stats.out_of_order += 1;
+            return Tick::OutOfOrder { latest };
+        }
+
+        self.latest.insert(symbol.to_string(), exchange);
+        let silence = (exchange - latest).to_std().unwrap_or_default();
+        if silence > self.gap_threshold {
+            self.stats.record_gap(silence);
+            Tick::Gap { since: latest }
+        } else {
+            Tick::InOrder
+        }
+    }
+}
+
+impl Default for FeedClock {
+    fn default() -> Self {
+        FeedClock::new(FeedClock::DEFAULT_GAP_THRESHOLD)
+    }
+}
@@ -1,98 +1,238 @@
-use crate::messages::{EventType, OrderEvent, Response};
-use matchbook::{Order, OrderError, OrderId, OrderType, Orderbook, Price, Quantity, Side};
-
-#[derive(Debug, thiserror::Error)]
-pub enum ParseError {
-    #[error("Invalid channel from Kraken")]
-    InvalidChannel,
-    #[error("Invalid response type from Kraken")]
-    InvalidType,
-    #[error("Response is empty")]
-    Empty,
-    #[error("Order Error: {0:?}")]
-    OrderError(#[from] OrderError),
+use crate::FeedAdapter;
+use crate::applier::{ApplyReport, EventOutcome, ParseError, apply_events};
+use crate::book::{Book, BookSet, BookSide};
+use crate::checksum::checksum;
+use crate::event::{BookEvent, OrderUpdate};
+use crate::messages::{Data, EventType, KrakenMessage, OrderEvent, Response};
+use crate::subscription::LEVEL3_CHANNEL;
+use crate::timing::{FeedClock, Tick};
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+/// Maps Kraken's level3 channel onto [`BookEvent`]s.
+///
+/// Each data block of a snapshot becomes a snapshot start, one add per order
+/// and a snapshot end. Blocks carrying a checksum are followed by a
+/// [`BookEvent::Checksum`] verified with Kraken's CRC32.
+///
+/// Update timestamps go through a [`FeedClock`], which counts out-of-order
+/// updates and turns silences longer than the gap threshold into
+/// [`BookEvent::Gap`]s.
+#[derive(Debug, Clone, Default)]
+pub struct KrakenAdapter {
+    clock: FeedClock,
 }
 
-pub fn process_message(orderbook: &mut Orderbook, message: Response) -> Result<(), ParseError> {
-    if message.channel != "level3" {
-        return Err(ParseError::InvalidChannel);
+impl KrakenAdapter {
+    pub fn new() -> KrakenAdapter {
+        KrakenAdapter::default()
+    }
+
+    pub fn with_gap_threshold(gap_threshold: Duration) -> KrakenAdapter {
+        KrakenAdapter {
+            clock: FeedClock::new(gap_threshold),
+        }
+    }
+
+    pub fn clock(&self) -> &FeedClock {
+        &self.clock
+    }
+
+    /// Applies one level3 message to `books`, reporting each order event's
+    /// outcome to `on_event` as it is applied.
+    pub fn process<F>(
+        &mut self,
+        books: &mut BookSet,
+        message: &Response,
+        on_event: F,
+    ) -> Result<ApplyReport, ParseError>
+    where
+        F: FnMut(EventOutcome<'_>),
+    {
+        self.process_at(books, message, Utc::now(), on_event)
+    }
+
+    /// [`KrakenAdapter::process`] for a message received at `received`, e.g.
+    /// one read back from a capture.
+    pub fn process_at<F>(
+        &mut self,
+        books: &mut BookSet,
+        message: &Response,
+        received: DateTime<Utc>,
+        on_event: F,
+    ) -> Result<ApplyReport, ParseError>
+    where
+        F: FnMut(EventOutcome<'_>),
+    {
+        let mut events = Vec::new();
+        self.normalize_response_at(message, received, &mut events)?;
+        apply_events(self, books, &events, on_event)
     }
 
-    if message.data.is_empty() {
-        return Err(ParseError::Empty);
+    /// Normalizes a level3 message received just now.
+    pub fn normalize_response(
+        &mut self,
+        message: &Response,
+        events: &mut Vec<BookEvent>,
+    ) -> Result<(), ParseError> {
+        self.normalize_response_at(message, Utc::now(), events)
     }
 
-    for data in &message.data {
-        for bid in &data.bids {
-            process_event(orderbook, bid, true)?;
+    /// Normalizes a level3 message received at `received`, which exchange
+    /// latency is measured against.
+    pub fn normalize_response_at(
+        &mut self,
+        message: &Response,
+        received: DateTime<Utc>,
+        events: &mut Vec<BookEvent>,
+    ) -> Result<(), ParseError> {
+        if message.channel != LEVEL3_CHANNEL {
+            return Err(ParseError::InvalidChannel);
         }
 
-        for ask in &data.asks {
-            process_event(orderbook, ask, false)?;
+        let snapshot = match message.message_type.as_str() {
+            "snapshot" => true,
+            "update" => false,
+            _ => return Err(ParseError::InvalidType),
+        };
+
+        if message.data.is_empty() {
+            return Err(ParseError::Empty);
         }
-    }
 
-    Ok(())
-}
+        for data in &message.data {
+            let symbol = data.symbol.as_deref().ok_or(ParseError::MissingSymbol)?;
 
-fn process_event(
-    orderbook: &mut Orderbook,
-    event: &OrderEvent,
-    is_bid: bool,
-) -> Result<(), ParseError> {
-    match event.event {
-        EventType::Add => {
-            let order = to_order(event, is_bid);
-            match orderbook.add_order(order.clone()) {
-                Ok(()) => {}
-                Err(OrderError::IdExists) => {
-                    // Order exists - this is a replace/update
-                    // Delete old and add new
-                    let _ = orderbook.cancel_order(order.order_id.clone());
-                    let _ = orderbook.add_order(order);
-                }
-                Err(e) => return Err(e.into()),
+            if snapshot {
+                // Snapshot orders carry the time they were placed, not sent
+                self.clock.reset(symbol, data.timestamp);
+                events.push(BookEvent::SnapshotStart {
+                    symbol: symbol.to_string(),
+                });
+            } else {
+                self.observe(symbol, data, received, events);
+            }
+
+            let bids = data.bids.iter().map(|event| (event, BookSide::Bid));
+            let asks = data.asks.iter().map(|event| (event, BookSide::Ask));
+            for (event, side) in bids.chain(asks) {
+                events.push(to_book_event(symbol, event, side, snapshot));
+            }
+
+            if snapshot {
+                events.push(BookEvent::SnapshotEnd {
+                    symbol: symbol.to_string(),
+                });
+            }
+
+            if let Some(expected) = data.checksum {
+                events.push(BookEvent::Checksum {
+                    symbol: symbol.to_string(),
+                    expected,
+                });
             }
         }
-        EventType::Modify => {
-            let id = to_order_id(&event.order_id);
-            let qty = to_quantity(event.order_qty);
-            orderbook.modify_order(id, qty)?;
-        }
-        EventType::Delete => {
-            let id = to_order_id(&event.order_id);
-            orderbook.cancel_order(id)?;
+
+        Ok(())
+    }
+
+    /// Runs a block's update timestamps through the clock, oldest first since
+    /// Kraken lists bids and asks separately.
+    fn observe(
+        &mut self,
+        symbol: &str,
+        data: &Data,
+        received: DateTime<Utc>,
+        events: &mut Vec<BookEvent>,
+    ) {
+        let mut timestamps: Vec<_> = data
+            .bids
+            .iter()
+            .chain(&data.asks)
+            .map(|event| event.timestamp)
+            .collect();
+        timestamps.sort();
+        for at in timestamps {
+            if let Tick::Gap { since } = self.clock.observe(symbol, at, received) {
+                events.push(BookEvent::Gap {
+                    symbol: symbol.to_string(),
+                    since,
+                    at,
+                });
+            }
         }
     }
-    Ok(())
 }
 
-pub fn to_order_id(kraken_id: &str) -> OrderId {
-    OrderId::new(kraken_id.to_string())
-}
-pub fn to_price(kraken_price: f64) -> Price {
-    Price::new((kraken_price * 100.0) as u64) // cents precision
-}
+impl FeedAdapter for KrakenAdapter {
+    type Message = KrakenMessage;
 
-pub fn to_quantity(kraken_qty: f64) -> Quantity {
-    Quantity((kraken_qty * 100_000_000.0) as u64) // 8 decimals for BTC qty is fine
+    fn normalize(
+        &mut self,
+        message: &KrakenMessage,
+        events: &mut Vec<BookEvent>,
+    ) -> Result<(), ParseError> {
+        match message {
+            KrakenMessage::Snapshot(response) | KrakenMessage::Update(response) => {
+                self.normalize_response(response, events)
+            }
+            KrakenMessage::Heartbeat => {
+                events.push(BookEvent::Heartbeat);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn checksum(&self, book: &Book) -> Option<u32> {
+        Some(checksum(&book.orders, &book.spec))
+    }
 }
 
-pub fn to_side(is_bid: bool) -> Side {
-    if is_bid { Side::Buy } else { Side::Sell }
+/// Applies one level3 message to `books`, see [`apply_events`]. Timestamps
+/// aren't tracked across calls; keep a [`KrakenAdapter`] for that.
+pub fn process_message(books: &mut BookSet, message: Response) -> Result<ApplyReport, ParseError> {
+    process_message_with(books, message, |_| {})
 }
 
-pub fn to_order(kraken_order: &OrderEvent, is_bid: bool) -> Order {
-    let order_id = to_order_id(&kraken_order.order_id);
-    let price = to_price(kraken_order.limit_price);
-    let quantity = to_quantity(kraken_order.order_qty);
-    let side = to_side(is_bid);
+/// [`process_message`], reporting each order event's outcome to `on_event`
+/// as it is applied.
+pub fn process_message_with<F>(
+    books: &mut BookSet,
+    message: Response,
+    on_event: F,
+) -> Result<ApplyReport, ParseError>
+where
+    F: FnMut(EventOutcome<'_>),
+{
+    KrakenAdapter::new().process(books, &message, on_event)
+}
 
-    Order::new(
-        order_id,
-        OrderType::GoodTillCancelled,
+fn to_book_event(symbol: &str, event: &OrderEvent, side: BookSide, snapshot: bool) -> BookEvent {
+    let symbol = symbol.to_string();
+    let order = || OrderUpdate {
+        order_id: event.order_id.clone(),
         side,
-        price,
-        quantity,
-    )
+        price: event.limit_price,
+        qty: event.order_qty,
+    };
+    // Snapshot orders carry no event type and are all resting adds
+    match event.event {
+        _ if snapshot => BookEvent::AddOrder {
+            symbol,
+            order: order(),
+        },
+        EventType::Add => BookEvent::AddOrder {
+            symbol,
+            order: order(),
+        },
+        EventType::Modify => BookEvent::ModifyOrder {
+            symbol,
+            order: order(),
+        },
+        EventType::Delete => BookEvent::DeleteOrder {
+            symbol,
+            order_id: event.order_id.clone(),
+        },
+    }
 }
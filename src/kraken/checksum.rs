@@ -0,0 +1,37 @@
+use crate::book::OrderIndex;
+use crate::instrument::InstrumentSpec;
+
+/// Number of price levels per side covered by Kraken's L3 checksum.
+pub const CHECKSUM_LEVELS: usize = 10;
+
+/// Kraken's CRC32 over the top of the book: every order of the best ten ask
+/// levels (lowest first) and then the best ten bid levels (highest first),
+/// each contributing its price and quantity in queue order.
+///
+/// Each value is formatted at the instrument's precision with the decimal
+/// point and leading zeros removed, so `0.03010` becomes `3010`.
+pub fn checksum(orders: &OrderIndex, spec: &InstrumentSpec) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buf = String::with_capacity(32);
+
+    let asks = orders.asks().take(CHECKSUM_LEVELS);
+    let bids = orders.bids().take(CHECKSUM_LEVELS);
+    for (price, ids) in asks.chain(bids) {
+        for id in ids {
+            let Some(order) = orders.get(id) else {
+                continue;
+            };
+            buf.clear();
+            push_field(&mut buf, &spec.format_price(price));
+            push_field(&mut buf, &spec.format_qty(order.qty));
+            hasher.update(buf.as_bytes());
+        }
+    }
+
+    hasher.finalize()
+}
+
+fn push_field(buf: &mut String, decimal: &str) {
+    let digits = decimal.replace('.', "");
+    buf.push_str(digits.trim_start_matches('0'));
+}
@@ -1,17 +1,25 @@
-use crate::messages::{SubscribeParams, SubscribeRequest, TokenResponse};
-use base64::{Engine as _, engine::general_purpose};
-use futures_util::{SinkExt, StreamExt};
-use hmac::{Hmac, Mac};
+use crate::messages::{KrakenMessage, MethodResponse, SubscribeRequest};
+use crate::recorder::{CaptureError, CaptureHeader, Recorder};
+use crate::stats::ConnectionStats;
+use crate::subscription::SubscriptionConfig;
+use crate::token::{Credentials, TOKEN_URL, TokenManager, WsToken};
+use futures_util::{SinkExt, Stream, StreamExt};
 use serde::Serialize;
-use sha2::{Digest, Sha256, Sha512};
-use std::env;
+use std::collections::VecDeque;
+use std::future::poll_fn;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 use tokio::net::TcpStream;
 use tokio_tungstenite::tungstenite::client::IntoClientRequest;
 use tokio_tungstenite::tungstenite::{Message, Utf8Bytes};
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
+use tracing::warn;
+
+const WS_URL: &str = "wss://ws-l3.kraken.com/v2";
 
 type KrakenStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
-type HmacSha512 = Hmac<Sha512>;
 
 #[derive(Debug, thiserror::Error)]
 pub enum ConnectionError {
@@ -19,6 +27,8 @@ pub enum ConnectionError {
     FailedToConnect(#[from] tokio_tungstenite::tungstenite::Error),
     #[error("Missing API Key")]
     MissingApiKey,
+    #[error("API secret is not valid base64")]
+    InvalidApiSecret,
     #[error("Invalid Request")]
     InvalidRequest(#[from] reqwest::Error),
     #[error("Missing Token")]
@@ -27,12 +37,246 @@ pub enum ConnectionError {
     ApiError(String),
     #[error("Invalid message")]
     InvalidMessage,
+    #[error("Invalid subscription: {0}")]
+    InvalidSubscription(String),
+    #[error("Disconnected from Kraken")]
+    Disconnected,
+    #[error("WebSocket token expired")]
+    TokenExpired,
+    #[error("Invalid endpoint: {0}")]
+    InvalidEndpoint(String),
+    #[error("Malformed message: {0}")]
+    Malformed(#[from] serde_json::Error),
+    #[error("{method} request {req_id} rejected for {symbol:?}: {reason}")]
+    Rejected {
+        method: String,
+        req_id: u64,
+        symbol: Option<String>,
+        reason: RejectReason,
+    },
+    #[error("Timed out waiting for a response to request {req_id}")]
+    AckTimeout { req_id: u64 },
+    #[error("Failed to record the feed")]
+    Recording(#[from] CaptureError),
+}
+
+/// Why Kraken refused a method request, classified from its error string.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RejectReason {
+    #[error("unknown symbol")]
+    UnknownSymbol,
+    #[error("invalid depth")]
+    InvalidDepth,
+    #[error("invalid token")]
+    InvalidToken,
+    #[error("{0}")]
+    Other(String),
+}
+
+impl RejectReason {
+    pub fn from_kraken(error: &str) -> RejectReason {
+        let lower = error.to_ascii_lowercase();
+        if lower.contains("token") || lower.contains("session") {
+            RejectReason::InvalidToken
+        } else if lower.contains("depth") {
+            RejectReason::InvalidDepth
+        } else if lower.contains("symbol") || lower.contains("currency pair") {
+            RejectReason::UnknownSymbol
+        } else {
+            RejectReason::Other(error.to_string())
+        }
+    }
+}
+
+/// Where the client fetches tokens from and streams data over. Both default to
+/// Kraken, and can be pointed at a local stand-in (`http://` and `ws://` work).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoints {
+    pub token_url: String,
+    pub ws_url: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Endpoints {
+            token_url: TOKEN_URL.to_string(),
+            ws_url: WS_URL.to_string(),
+        }
+    }
+}
+
+/// What the consumer of a supervised connection should do next.
+#[allow(clippy::large_enum_variant)]
+pub enum FeedEvent {
+    Message(KrakenMessage),
+    /// The connection was re-established; any mirrored book is stale and must be
+    /// cleared and rebuilt from the snapshot that follows.
+    Resync,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Give up after this many failed attempts in a row, `None` retries forever.
+    pub max_attempts: Option<u32>,
+}
+
+impl ReconnectPolicy {
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(30),
+            max_attempts: Some(10),
+        }
+    }
 }
 
-#[allow(dead_code)]
 pub struct KrakenClient {
     stream: KrakenStream,
-    url: String,
+    endpoints: Endpoints,
+    subscription: SubscriptionConfig,
+    tokens: TokenManager,
+    reconnect: ReconnectPolicy,
+    connection: ConnectionStats,
+    disconnected_at: Option<Instant>,
+    next_req_id: u64,
+    ack_timeout: Duration,
+    /// Messages that arrived while waiting for a method response
+    pending: VecDeque<KrakenMessage>,
+    /// Counts up from 1 with each socket opened
+    connection_id: u32,
+    recorder: Option<Recorder>,
+    /// Symbols whose resubscribe failed, still waiting for a fresh snapshot
+    resyncs: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct KrakenClientBuilder {
+    subscription: SubscriptionConfig,
+    reconnect: ReconnectPolicy,
+    token_refresh_margin: Option<Duration>,
+    ack_timeout: Option<Duration>,
+    endpoints: Endpoints,
+    credentials: Option<Credentials>,
+    record_to: Option<PathBuf>,
+}
+
+impl KrakenClientBuilder {
+    pub fn new() -> KrakenClientBuilder {
+        KrakenClientBuilder::default()
+    }
+
+    pub fn channel(mut self, channel: impl Into<String>) -> Self {
+        self.subscription.channel = channel.into();
+        self
+    }
+
+    pub fn symbols<I, S>(mut self, symbols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.subscription.symbols = symbols.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn depth(mut self, depth: u32) -> Self {
+        self.subscription.depth = depth;
+        self
+    }
+
+    pub fn snapshot(mut self, snapshot: bool) -> Self {
+        self.subscription.snapshot = snapshot;
+        self
+    }
+
+    pub fn reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = policy;
+        self
+    }
+
+    /// How long before expiry a token is replaced.
+    pub fn token_refresh_margin(mut self, margin: Duration) -> Self {
+        self.token_refresh_margin = Some(margin);
+        self
+    }
+
+    pub fn endpoints(mut self, endpoints: Endpoints) -> Self {
+        self.endpoints = endpoints;
+        self
+    }
+
+    /// How long to wait for Kraken to answer a subscribe or unsubscribe.
+    pub fn ack_timeout(mut self, timeout: Duration) -> Self {
+        self.ack_timeout = Some(timeout);
+        self
+    }
+
+    /// Use these credentials instead of reading them from the environment.
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Records every frame received to a new capture at `path`, see [`Recorder`].
+    pub fn record_to(mut self, path: impl Into<PathBuf>) -> Self {
+        self.record_to = Some(path.into());
+        self
+    }
+
+    pub async fn connect(self) -> Result<KrakenClient, ConnectionError> {
+        self.subscription.validate()?;
+        if !self.endpoints.ws_url.starts_with("ws://")
+            && !self.endpoints.ws_url.starts_with("wss://")
+        {
+            return Err(ConnectionError::InvalidEndpoint(self.endpoints.ws_url));
+        }
+
+        let credentials = match self.credentials {
+            Some(credentials) => credentials,
+            None => Credentials::from_env()?,
+        };
+        let mut tokens = TokenManager::new(credentials, &self.endpoints.token_url)?;
+        if let Some(margin) = self.token_refresh_margin {
+            tokens = tokens.with_refresh_margin(margin);
+        }
+
+        let recorder = match &self.record_to {
+            Some(path) => Some(Recorder::create(
+                path,
+                CaptureHeader::kraken(&self.subscription),
+            )?),
+            None => None,
+        };
+
+        let stream = KrakenClient::connect_stream(&self.endpoints.ws_url).await?;
+        let mut client = KrakenClient {
+            stream,
+            endpoints: self.endpoints,
+            subscription: self.subscription,
+            tokens,
+            reconnect: self.reconnect,
+            connection: ConnectionStats::default(),
+            disconnected_at: None,
+            next_req_id: 1,
+            ack_timeout: self.ack_timeout.unwrap_or(Duration::from_secs(10)),
+            pending: VecDeque::new(),
+            connection_id: 1,
+            recorder,
+            resyncs: Vec::new(),
+        };
+        client.resubscribe().await?;
+        Ok(client)
+    }
 }
 
 #[derive(Serialize)]
@@ -41,113 +285,407 @@ pub struct TokenRequest {
 }
 
 impl KrakenClient {
-    pub async fn read(&mut self) -> Result<String, ConnectionError> {
-        if let Some(res) = self.stream.next().await
-            && let Ok(Message::Text(msg)) = res
-        {
-            Ok(msg.to_string())
-        } else {
-            Err(ConnectionError::InvalidMessage)
-        }
+    pub fn builder() -> KrakenClientBuilder {
+        KrakenClientBuilder::new()
+    }
+
+    pub fn subscription(&self) -> &SubscriptionConfig {
+        &self.subscription
     }
 
-    fn kraken_api_sign(path: &str, nonce: &str, postdata: &str, api_secret: &str) -> String {
-        // 1. SHA256(nonce + postdata)
-        let mut sha256 = Sha256::new();
-        sha256.update(nonce.as_bytes());
-        sha256.update(postdata.as_bytes());
-        let hash = sha256.finalize();
+    pub fn endpoints(&self) -> &Endpoints {
+        &self.endpoints
+    }
 
-        // 2. path + hash
-        let mut message = Vec::with_capacity(path.len() + hash.len());
-        message.extend_from_slice(path.as_bytes());
-        message.extend_from_slice(&hash);
+    pub fn connection_stats(&self) -> &ConnectionStats {
+        &self.connection
+    }
 
-        // 3. HMAC-SHA512
-        let secret = general_purpose::STANDARD
-            .decode(api_secret)
-            .expect("invalid base64 secret");
+    pub fn token(&self) -> Option<&WsToken> {
+        self.tokens.current()
+    }
 
-        let mut mac = HmacSha512::new_from_slice(&secret).expect("HMAC can take key of any size");
-        mac.update(&message);
+    /// Identifies the current socket; frames from one connection share an id.
+    pub fn connection_id(&self) -> u32 {
+        self.connection_id
+    }
 
-        // 4. base64 encode
-        general_purpose::STANDARD.encode(mac.finalize().into_bytes())
+    pub fn recorder(&self) -> Option<&Recorder> {
+        self.recorder.as_ref()
     }
 
-    pub async fn new() -> Result<KrakenClient, ConnectionError> {
-        dotenvy::dotenv().ok();
-        let api_key = env::var("KRAKEN_API_KEY").map_err(|_| ConnectionError::MissingApiKey)?;
-        let api_secret =
-            env::var("KRAKEN_PRIVATE_KEY").map_err(|_| ConnectionError::MissingApiKey)?;
-        let nonce = chrono::Utc::now().timestamp_millis().to_string();
-        let postdata = format!("nonce={}", nonce);
-        let url = "https://api.kraken.com/0/private/GetWebSocketsToken";
-        let path = "/0/private/GetWebSocketsToken";
-        let ws = "wss://ws-l3.kraken.com/v2";
+    pub fn recorder_mut(&mut self) -> Option<&mut Recorder> {
+        self.recorder.as_mut()
+    }
 
-        let api_sign = Self::kraken_api_sign(path, &nonce, &postdata, &api_secret);
-        let token = Self::get_ws_token(url, api_key, api_sign, postdata)
-            .await
-            .unwrap();
+    /// Messages read off the socket but not yet returned, e.g. updates that
+    /// arrived while waiting for a subscribe acknowledgement.
+    pub fn pending_messages(&self) -> usize {
+        self.pending.len()
+    }
 
-        let stream = Self::connect_with_token(ws, token).await?;
-        Ok(KrakenClient {
-            stream,
-            url: ws.to_string(),
-        })
-    }
-
-    async fn get_ws_token(
-        url: &str,
-        api_key: String,
-        api_sign: String,
-        postdata: String,
-    ) -> Result<String, ConnectionError> {
-        let client = reqwest::Client::new();
-        let response = client
-            .post(url)
-            .header("API-Key", api_key)
-            .header("API-Sign", api_sign)
-            .header("Content-Type", "application/x-www-form-urlencoded")
-            .header("Accept", "application/json")
-            .body(postdata)
-            .send()
-            .await?;
-
-        let token: TokenResponse = response
-            .json()
+    /// Stops recording and hands back the recorder, e.g. to
+    /// [`finish`](Recorder::finish) the capture.
+    pub fn take_recorder(&mut self) -> Option<Recorder> {
+        self.recorder.take()
+    }
+
+    /// Reads the next message. Pings are answered here and never returned,
+    /// a close frame is returned once as [`KrakenMessage::Closed`].
+    pub async fn read(&mut self) -> Result<KrakenMessage, ConnectionError> {
+        self.next()
             .await
-            .map_err(ConnectionError::InvalidRequest)?;
+            .unwrap_or(Err(ConnectionError::Disconnected))
+    }
+
+    /// Reads the next message, transparently reconnecting with backoff if the
+    /// socket drops. Returns [`FeedEvent::Resync`] once a new connection is up.
+    /// Tokens are only fetched when a (re)subscribe needs one, never while
+    /// reading.
+    pub async fn next_event(&mut self) -> Result<FeedEvent, ConnectionError> {
+        match self.read().await {
+            Ok(KrakenMessage::Closed { code, reason }) => {
+                warn!("Kraken closed the connection ({:?}): {}", code, reason);
+                self.disconnected_at.get_or_insert_with(Instant::now);
+                self.reconnect().await?;
+                Ok(FeedEvent::Resync)
+            }
+            Ok(msg) => {
+                // The books are stale until the new connection's snapshot,
+                // not just until the socket is back
+                if matches!(msg, KrakenMessage::Snapshot(_))
+                    && let Some(since) = self.disconnected_at.take()
+                {
+                    self.connection.record_stale(since.elapsed());
+                }
+                Ok(FeedEvent::Message(msg))
+            }
+            Err(ConnectionError::Disconnected) => {
+                self.disconnected_at.get_or_insert_with(Instant::now);
+                self.reconnect().await?;
+                Ok(FeedEvent::Resync)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Adds symbols to the live subscription without reconnecting. Symbols
+    /// Kraken accepted stay subscribed even if others in the batch fail.
+    pub async fn subscribe<I, S>(&mut self, symbols: I) -> Result<(), ConnectionError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let symbols: Vec<String> = symbols.into_iter().map(Into::into).collect();
+        let mut next = self.subscription.clone();
+        next.symbols.extend(symbols.iter().cloned());
+        next.validate()?;
+
+        let (accepted, result) = self.request("subscribe", symbols).await;
+        self.subscription.symbols.extend(accepted);
+        self.record_subscription()?;
+        result
+    }
 
-        if !token.error.is_empty() {
-            return Err(ConnectionError::ApiError(token.error.join(", ")));
+    /// Removes symbols from the live subscription without reconnecting.
+    pub async fn unsubscribe<I, S>(&mut self, symbols: I) -> Result<(), ConnectionError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let symbols: Vec<String> = symbols.into_iter().map(Into::into).collect();
+        if let Some(symbol) = symbols
+            .iter()
+            .find(|symbol| !self.subscription.symbols.contains(symbol))
+        {
+            return Err(ConnectionError::InvalidSubscription(format!(
+                "not subscribed to {:?}",
+                symbol
+            )));
         }
-        let result = token.result.ok_or(ConnectionError::MissingToken)?;
-        Ok(result.token)
+
+        let (removed, result) = self.request("unsubscribe", symbols).await;
+        self.subscription
+            .symbols
+            .retain(|symbol| !removed.contains(symbol));
+        self.record_subscription()?;
+        result
     }
 
-    async fn connect_with_token(url: &str, token: String) -> Result<KrakenStream, ConnectionError> {
-        let request = url.into_client_request().unwrap();
-        let (mut stream, _) = connect_async(request).await?;
+    /// Drops and re-requests one symbol so Kraken sends a fresh snapshot,
+    /// e.g. after its mirrored book failed a checksum. A symbol whose
+    /// resubscribe fails is kept for [`retry_resyncs`](Self::retry_resyncs).
+    pub async fn resubscribe_symbol(&mut self, symbol: &str) -> Result<(), ConnectionError> {
+        let mut result = Ok(());
+        if self.subscription.symbols.iter().any(|s| s == symbol) {
+            result = self.unsubscribe([symbol]).await;
+        }
+        if result.is_ok() {
+            result = self.subscribe([symbol]).await;
+        }
+
+        self.resyncs.retain(|s| s != symbol);
+        if result.is_err() {
+            self.resyncs.push(symbol.to_string());
+        }
+        result
+    }
+
+    /// Symbols a resubscribe failed for, so their books are still waiting
+    /// for a snapshot.
+    pub fn pending_resyncs(&self) -> &[String] {
+        &self.resyncs
+    }
+
+    /// Resubscribes every symbol in [`pending_resyncs`](Self::pending_resyncs),
+    /// keeping those that fail again. Returns the first failure.
+    pub async fn retry_resyncs(&mut self) -> Result<(), ConnectionError> {
+        let mut first = Ok(());
+        for symbol in self.resyncs.clone() {
+            let result = self.resubscribe_symbol(&symbol).await;
+            if first.is_ok() {
+                first = result;
+            }
+        }
+        first
+    }
+
+    /// Subscribes the current connection to every configured symbol.
+    async fn resubscribe(&mut self) -> Result<(), ConnectionError> {
+        let symbols = self.subscription.symbols.clone();
+        self.request("subscribe", symbols).await.1?;
+        self.record_subscription()
+    }
+
+    fn record_subscription(&mut self) -> Result<(), ConnectionError> {
+        if let Some(recorder) = &mut self.recorder {
+            recorder
+                .record_subscription(self.connection_id, &self.subscription)
+                .map_err(CaptureError::from)?;
+        }
+        Ok(())
+    }
 
-        let params = SubscribeParams {
-            channel: "level3".to_string(),
-            symbol: vec!["ETH/BTC".to_string()],
-            depth: Some(1000),
-            snapshot: Some(true),
-            token,
-            req_id: None,
+    /// Tees a received frame, or the end of the connection, to the recorder.
+    fn record_frame(&mut self, frame: Option<&str>) -> Result<(), ConnectionError> {
+        if let Some(recorder) = &mut self.recorder {
+            match frame {
+                Some(frame) => recorder.record_frame(self.connection_id, frame),
+                None => recorder.record_disconnect(self.connection_id),
+            }
+            .map_err(CaptureError::from)?;
+        }
+        Ok(())
+    }
+
+    /// Sends a method request with a fresh `req_id` and waits for Kraken's
+    /// response for each symbol. Returns the symbols that succeeded alongside
+    /// the first failure, if any.
+    async fn request(
+        &mut self,
+        method: &str,
+        symbols: Vec<String>,
+    ) -> (Vec<String>, Result<(), ConnectionError>) {
+        if symbols.is_empty() {
+            return (Vec::new(), Ok(()));
+        }
+        let token = match self.tokens.token().await {
+            Ok(token) => token,
+            Err(e) => return (Vec::new(), Err(e)),
         };
 
+        let req_id = self.next_req_id;
+        self.next_req_id += 1;
+
+        let params = match method {
+            "subscribe" => self
+                .subscription
+                .subscribe_params(symbols.clone(), token.value, req_id),
+            _ => self
+                .subscription
+                .unsubscribe_params(symbols.clone(), token.value, req_id),
+        };
         let body = SubscribeRequest {
-            method: "subscribe".to_string(),
+            method: method.to_string(),
             params,
         };
 
         let msg = serde_json::to_string(&body).unwrap();
-        stream.send(Message::Text(Utf8Bytes::from(msg))).await?;
+        if let Err(e) = self.stream.send(Message::Text(Utf8Bytes::from(msg))).await {
+            return (Vec::new(), Err(e.into()));
+        }
+
+        self.await_responses(req_id, &symbols).await
+    }
+
+    async fn await_responses(
+        &mut self,
+        req_id: u64,
+        symbols: &[String],
+    ) -> (Vec<String>, Result<(), ConnectionError>) {
+        let deadline = tokio::time::Instant::now() + self.ack_timeout;
+        let mut succeeded = Vec::new();
+        let mut failure: Option<MethodResponse> = None;
+        let mut answered = 0;
+
+        while answered < symbols.len() {
+            let next = tokio::time::timeout_at(deadline, poll_fn(|cx| self.poll_frame(cx))).await;
+            let message = match next {
+                Err(_) => return (succeeded, Err(ConnectionError::AckTimeout { req_id })),
+                Ok(None) | Ok(Some(Ok(KrakenMessage::Closed { .. }))) => {
+                    return (succeeded, Err(ConnectionError::Disconnected));
+                }
+                Ok(Some(Err(ConnectionError::Malformed(e)))) => {
+                    warn!("Skipping malformed message: {}", e);
+                    continue;
+                }
+                Ok(Some(Err(e))) => return (succeeded, Err(e)),
+                Ok(Some(Ok(message))) => message,
+            };
+
+            match message {
+                KrakenMessage::Subscribed(ack) | KrakenMessage::Unsubscribed(ack)
+                    if ack.req_id == Some(req_id) =>
+                {
+                    answered += 1;
+                    let symbol = ack.result.and_then(|result| result.symbol).or(ack.symbol);
+                    succeeded.extend(symbol);
+                }
+                KrakenMessage::MethodError(ack) if ack.req_id == Some(req_id) => {
+                    // An error without a symbol refers to the whole request
+                    answered = if ack.symbol.is_some() {
+                        answered + 1
+                    } else {
+                        symbols.len()
+                    };
+                    failure.get_or_insert(ack);
+                }
+                other => self.pending.push_back(other),
+            }
+        }
+
+        let result = match failure {
+            Some(ack) => Err(ConnectionError::Rejected {
+                reason: RejectReason::from_kraken(ack.error.as_deref().unwrap_or_default()),
+                method: ack.method,
+                req_id,
+                symbol: ack.symbol,
+            }),
+            None => Ok(()),
+        };
+        (succeeded, result)
+    }
+
+    async fn reconnect(&mut self) -> Result<(), ConnectionError> {
+        let mut attempt = 0;
+        loop {
+            let delay = self.reconnect.backoff(attempt);
+            warn!("Kraken connection lost, reconnecting in {:?}", delay);
+            tokio::time::sleep(delay).await;
 
+            match self.open().await {
+                Ok(()) => {
+                    self.connection.reconnects += 1;
+                    return Ok(());
+                }
+                Err(e) => {
+                    if matches!(
+                        e,
+                        ConnectionError::TokenExpired
+                            | ConnectionError::Rejected {
+                                reason: RejectReason::InvalidToken,
+                                ..
+                            }
+                    ) {
+                        self.tokens.invalidate();
+                    }
+                    attempt += 1;
+                    if self
+                        .reconnect
+                        .max_attempts
+                        .is_some_and(|max| attempt >= max)
+                    {
+                        return Err(e);
+                    }
+                    warn!("Reconnect attempt {} failed: {}", attempt, e);
+                }
+            }
+        }
+    }
+
+    pub async fn new(subscription: SubscriptionConfig) -> Result<KrakenClient, ConnectionError> {
+        KrakenClientBuilder {
+            subscription,
+            ..KrakenClientBuilder::default()
+        }
+        .connect()
+        .await
+    }
+
+    /// Replaces the stream with a new connection subscribed to every configured symbol.
+    async fn open(&mut self) -> Result<(), ConnectionError> {
+        // Make sure the token is good before opening a socket that needs it
+        self.tokens.token().await?;
+        self.stream = Self::connect_stream(&self.endpoints.ws_url).await?;
+        self.connection_id += 1;
+        self.pending.clear();
+        self.resubscribe().await
+    }
+
+    async fn connect_stream(url: &str) -> Result<KrakenStream, ConnectionError> {
+        let request = url.into_client_request()?;
+        let (stream, _) = connect_async(request).await?;
         Ok(stream)
     }
+
+    fn poll_frame(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<KrakenMessage, ConnectionError>>> {
+        loop {
+            let frame = match self.stream.poll_next_unpin(cx) {
+                Poll::Ready(frame) => frame,
+                Poll::Pending => return Poll::Pending,
+            };
+            let recorded = match &frame {
+                Some(Ok(Message::Text(msg))) => self.record_frame(Some(msg.as_str())),
+                Some(Ok(Message::Close(_)) | Err(_)) | None => self.record_frame(None),
+                Some(Ok(_)) => Ok(()),
+            };
+            if let Err(e) = recorded {
+                return Poll::Ready(Some(Err(e)));
+            }
+            return Poll::Ready(match frame {
+                Some(Ok(Message::Text(msg))) => {
+                    Some(KrakenMessage::parse(&msg).map_err(ConnectionError::from))
+                }
+                Some(Ok(Message::Ping(_))) => {
+                    // tungstenite has queued the pong, push it out now
+                    let _ = self.stream.poll_flush_unpin(cx);
+                    continue;
+                }
+                Some(Ok(Message::Pong(_) | Message::Frame(_))) => continue,
+                Some(Ok(Message::Binary(_))) => Some(Err(ConnectionError::InvalidMessage)),
+                Some(Ok(Message::Close(frame))) => Some(Ok(KrakenMessage::Closed {
+                    code: frame.as_ref().map(|frame| u16::from(frame.code)),
+                    reason: frame
+                        .map(|frame| frame.reason.to_string())
+                        .unwrap_or_default(),
+                })),
+                Some(Err(_)) => Some(Err(ConnectionError::Disconnected)),
+                None => None,
+            });
+        }
+    }
+}
+
+impl Stream for KrakenClient {
+    type Item = Result<KrakenMessage, ConnectionError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(message) = self.pending.pop_front() {
+            return Poll::Ready(Some(Ok(message)));
+        }
+        self.poll_frame(cx)
+    }
 }
@@ -0,0 +1,219 @@
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use serde_json::value::RawValue;
+use std::fmt;
+use std::str::FromStr;
+
+/// Largest number of significant digits a [`Decimal`] can hold.
+const MAX_DIGITS: usize = 38;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DecimalError {
+    #[error("{0:?} is not a decimal number")]
+    Syntax(String),
+    #[error("value is negative")]
+    Negative,
+    #[error("value has more than {precision} decimal places")]
+    TooPrecise { precision: u32 },
+    #[error("value is out of range")]
+    Overflow,
+    #[error("price is not a multiple of the tick size {tick_size}")]
+    OffTick { tick_size: u64 },
+}
+
+/// A decimal number read straight from its JSON text, so no precision is lost
+/// on the way to a fixed-point price or quantity.
+///
+/// The value is `mantissa * 10^-scale`, normalised so the mantissa has no
+/// trailing zeros after the decimal point.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Decimal {
+    mantissa: u128,
+    scale: u32,
+    negative: bool,
+}
+
+impl Decimal {
+    pub fn new(mantissa: u128, scale: u32) -> Decimal {
+        Decimal {
+            mantissa,
+            scale,
+            negative: false,
+        }
+        .normalised()
+    }
+
+    pub fn mantissa(&self) -> u128 {
+        self.mantissa
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa == 0
+    }
+
+    /// `self - other`, or `None` if the result would be negative.
+    pub fn checked_sub(&self, other: &Decimal) -> Option<Decimal> {
+        if self.negative || other.negative {
+            return None;
+        }
+        let scale = self.scale.max(other.scale);
+        let lhs = self
+            .mantissa
+            .checked_mul(10u128.checked_pow(scale - self.scale)?)?;
+        let rhs = other
+            .mantissa
+            .checked_mul(10u128.checked_pow(scale - other.scale)?)?;
+        Some(Decimal::new(lhs.checked_sub(rhs)?, scale))
+    }
+
+    /// The value as an integer count of `10^-precision` units.
+    ///
+    /// Fails rather than rounds when the value has more decimal places than
+    /// `precision` allows, so the result is always exactly the input.
+    pub fn rescale(&self, precision: u32) -> Result<u64, DecimalError> {
+        if self.negative {
+            return Err(DecimalError::Negative);
+        }
+        if self.scale > precision {
+            return Err(DecimalError::TooPrecise { precision });
+        }
+
+        let factor = 10u128
+            .checked_pow(precision - self.scale)
+            .ok_or(DecimalError::Overflow)?;
+        let scaled = self
+            .mantissa
+            .checked_mul(factor)
+            .ok_or(DecimalError::Overflow)?;
+        u64::try_from(scaled).map_err(|_| DecimalError::Overflow)
+    }
+
+    fn normalised(mut self) -> Decimal {
+        while self.scale > 0 && self.mantissa.is_multiple_of(10) {
+            self.mantissa /= 10;
+            self.scale -= 1;
+        }
+        if self.mantissa == 0 {
+            self.scale = 0;
+            self.negative = false;
+        }
+        self
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = DecimalError;
+
+    /// Parses JSON number syntax: an optional minus sign, integer digits, an
+    /// optional fraction and an optional exponent.
+    fn from_str(text: &str) -> Result<Decimal, DecimalError> {
+        let syntax = || DecimalError::Syntax(text.to_string());
+
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let (number, exponent) = match unsigned.find(['e', 'E']) {
+            Some(at) => {
+                let exponent = &unsigned[at + 1..];
+                let exponent = exponent.strip_prefix('+').unwrap_or(exponent);
+                (
+                    &unsigned[..at],
+                    exponent.parse::<i64>().map_err(|_| syntax())?,
+                )
+            }
+            None => (unsigned, 0),
+        };
+        let (int, frac) = match number.split_once('.') {
+            Some((int, frac)) if !frac.is_empty() => (int, frac),
+            Some(_) => return Err(syntax()),
+            None => (number, ""),
+        };
+        let all_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if int.is_empty() || !all_digits(int) || !all_digits(frac) {
+            return Err(syntax());
+        }
+
+        let digits = format!("{}{}", int, frac);
+        let digits = digits.trim_start_matches('0');
+        // Exponents anywhere in i64 are valid JSON, so the scale can overflow
+        let mut scale = i64::try_from(frac.len())
+            .ok()
+            .and_then(|len| len.checked_sub(exponent))
+            .ok_or(DecimalError::Overflow)?;
+
+        // Trailing zeros carry no value, so drop them before sizing the mantissa
+        let mut digits = digits.to_string();
+        while scale > 0 && digits.ends_with('0') {
+            digits.pop();
+            scale -= 1;
+        }
+        if digits.is_empty() {
+            return Ok(Decimal::new(0, 0));
+        }
+        if scale < 0 {
+            let zeros =
+                usize::try_from(scale.unsigned_abs()).map_err(|_| DecimalError::Overflow)?;
+            if digits
+                .len()
+                .checked_add(zeros)
+                .is_none_or(|len| len > MAX_DIGITS)
+            {
+                return Err(DecimalError::Overflow);
+            }
+            digits.push_str(&"0".repeat(zeros));
+            scale = 0;
+        }
+        if digits.len() > MAX_DIGITS {
+            return Err(DecimalError::Overflow);
+        }
+
+        Ok(Decimal {
+            mantissa: digits.parse().map_err(|_| DecimalError::Overflow)?,
+            scale: u32::try_from(scale).map_err(|_| DecimalError::Overflow)?,
+            negative,
+        }
+        .normalised())
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negative {
+            f.write_str("-")?;
+        }
+        let digits = self.mantissa.to_string();
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return f.write_str(&digits);
+        }
+        if digits.len() > scale {
+            let (int, frac) = digits.split_at(digits.len() - scale);
+            write!(f, "{}.{}", int, frac)
+        } else {
+            write!(f, "0.{:0>width$}", digits, width = scale)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    /// Reads the number's original text, never going through `f64`. Numbers
+    /// quoted as strings, as some venues send them, are accepted too.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+        let raw = Box::<RawValue>::deserialize(deserializer)?;
+        let text = raw.get();
+        let text = text
+            .strip_prefix('"')
+            .and_then(|quoted| quoted.strip_suffix('"'))
+            .unwrap_or(text);
+        text.parse().map_err(D::Error::custom)
+    }
+}
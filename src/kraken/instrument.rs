@@ -0,0 +1,95 @@
+use crate::decimal::{Decimal, DecimalError};
+use serde::{Deserialize, Serialize};
+
+/// Decimal precision of a Kraken pair, as published on its `instrument` channel.
+///
+/// Prices and quantities are held as integers in the smallest unit the pair
+/// can express, so `0.03011` on a 5 decimal pair is `3011`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstrumentSpec {
+    pub price_precision: u32,
+    pub qty_precision: u32,
+    /// Minimum price increment, in scaled price units
+    pub tick_size: u64,
+}
+
+/// Pairs we mirror, with the precision and `price_increment` Kraken quotes
+/// them at.
+const KNOWN_INSTRUMENTS: [(&str, InstrumentSpec); 3] = [
+    // price_increment 0.00001
+    ("ETH/BTC", InstrumentSpec::new(5, 8).with_tick_size(1)),
+    // price_increment 0.1
+    ("BTC/USD", InstrumentSpec::new(1, 8).with_tick_size(1)),
+    // price_increment 0.01
+    ("ETH/USD", InstrumentSpec::new(2, 8).with_tick_size(1)),
+];
+
+impl InstrumentSpec {
+    pub const fn new(price_precision: u32, qty_precision: u32) -> InstrumentSpec {
+        InstrumentSpec {
+            price_precision,
+            qty_precision,
+            tick_size: 1,
+        }
+    }
+
+    pub const fn with_tick_size(mut self, tick_size: u64) -> InstrumentSpec {
+        self.tick_size = tick_size;
+        self
+    }
+
+    pub fn for_symbol(symbol: &str) -> Option<InstrumentSpec> {
+        KNOWN_INSTRUMENTS
+            .iter()
+            .find(|(known, _)| *known == symbol)
+            .map(|(_, spec)| *spec)
+    }
+
+    /// Converts `price` to scaled units exactly, failing if it is finer than
+    /// the pair's precision or falls between ticks.
+    pub fn scale_price(&self, price: &Decimal) -> Result<u64, DecimalError> {
+        let scaled = price.rescale(self.price_precision)?;
+        if !self.is_on_tick(scaled) {
+            return Err(DecimalError::OffTick {
+                tick_size: self.tick_size,
+            });
+        }
+        Ok(scaled)
+    }
+
+    pub fn scale_qty(&self, qty: &Decimal) -> Result<u64, DecimalError> {
+        qty.rescale(self.qty_precision)
+    }
+
+    pub fn format_price(&self, price: u64) -> String {
+        format_scaled(price, self.price_precision)
+    }
+
+    pub fn format_qty(&self, qty: u64) -> String {
+        format_scaled(qty, self.qty_precision)
+    }
+
+    pub fn is_on_tick(&self, price: u64) -> bool {
+        self.tick_size == 0 || price.is_multiple_of(self.tick_size)
+    }
+}
+
+impl Default for InstrumentSpec {
+    /// Kraken's finest precision, for pairs missing from the known table.
+    fn default() -> Self {
+        InstrumentSpec::new(8, 8)
+    }
+}
+
+fn format_scaled(value: u64, precision: u32) -> String {
+    if precision == 0 {
+        return value.to_string();
+    }
+    let unit = 10u64.pow(precision);
+    format!(
+        "{}.{:0width$}",
+        value / unit,
+        value % unit,
+        width = precision as usize
+    )
+}
@@ -1,3 +1,6 @@
+use crate::decimal::Decimal;
+use chrono::{DateTime, Utc};
+use serde::de::Error as _;
 use serde::{Deserialize, Serialize};
 
 #[allow(dead_code)]
@@ -7,20 +10,20 @@ pub struct TokenResponse {
     pub error: Vec<String>,
 }
 
-#[allow(dead_code)]
 #[derive(Deserialize)]
 pub struct TokenResponseResult {
     pub token: String,
-    expires: u64,
+    /// Seconds the token can be used to subscribe
+    pub expires: u64,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SubscribeRequest {
     pub method: String,
     pub params: SubscribeParams,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SubscribeParams {
     pub channel: String,
     pub symbol: Vec<String>,
@@ -33,7 +36,7 @@ pub struct SubscribeParams {
     pub req_id: Option<u64>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Response {
     pub channel: String,
     #[serde(rename = "type")]
@@ -41,31 +44,31 @@ pub struct Response {
     pub data: Vec<Data>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Data {
     #[serde(default)]
     pub checksum: Option<u32>,
     #[serde(default)]
     pub symbol: Option<String>,
     #[serde(default)]
-    pub timestamp: Option<String>,
+    pub timestamp: Option<DateTime<Utc>>,
     #[serde(default)]
     pub bids: Vec<OrderEvent>,
     #[serde(default)]
     pub asks: Vec<OrderEvent>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct OrderEvent {
     #[serde(default = "default_event")]
     pub event: EventType,
     pub order_id: String,
-    pub limit_price: f64,
-    pub order_qty: f64,
-    pub timestamp: String,
+    pub limit_price: Decimal,
+    pub order_qty: Decimal,
+    pub timestamp: DateTime<Utc>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 pub enum EventType {
     #[serde(rename = "add")]
     Add,
@@ -78,3 +81,123 @@ pub enum EventType {
 fn default_event() -> EventType {
     EventType::Add
 }
+
+/// Reply to a method request such as `subscribe`, `unsubscribe` or `ping`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MethodResponse {
+    pub method: String,
+    #[serde(default)]
+    pub success: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub req_id: Option<u64>,
+    #[serde(default)]
+    pub symbol: Option<String>,
+    #[serde(default)]
+    pub result: Option<MethodResult>,
+    #[serde(default)]
+    pub time_in: Option<String>,
+    #[serde(default)]
+    pub time_out: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MethodResult {
+    #[serde(default)]
+    pub channel: Option<String>,
+    #[serde(default)]
+    pub symbol: Option<String>,
+    #[serde(default)]
+    pub depth: Option<u32>,
+    #[serde(default)]
+    pub snapshot: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Status {
+    pub system: String,
+    #[serde(default)]
+    pub api_version: Option<String>,
+    #[serde(default)]
+    pub connection_id: Option<u64>,
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StatusMessage {
+    data: Vec<Status>,
+}
+
+/// Just enough of a frame to decide which type it should be parsed as.
+#[derive(Deserialize)]
+struct Envelope {
+    #[serde(default)]
+    channel: Option<String>,
+    #[serde(default)]
+    method: Option<String>,
+    #[serde(rename = "type", default)]
+    message_type: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum KrakenMessage {
+    Snapshot(Response),
+    Update(Response),
+    Heartbeat,
+    Status(Vec<Status>),
+    Subscribed(MethodResponse),
+    Unsubscribed(MethodResponse),
+    Pong(MethodResponse),
+    /// A method request Kraken refused
+    MethodError(MethodResponse),
+    /// The server closed the connection
+    Closed {
+        code: Option<u16>,
+        reason: String,
+    },
+}
+
+impl KrakenMessage {
+    pub fn parse(text: &str) -> Result<KrakenMessage, serde_json::Error> {
+        let envelope: Envelope = serde_json::from_str(text)?;
+
+        if let Some(method) = envelope.method {
+            let response: MethodResponse = serde_json::from_str(text)?;
+            return Ok(match method.as_str() {
+                "pong" => KrakenMessage::Pong(response),
+                _ if !response.success => KrakenMessage::MethodError(response),
+                "subscribe" => KrakenMessage::Subscribed(response),
+                "unsubscribe" => KrakenMessage::Unsubscribed(response),
+                other => {
+                    return Err(serde_json::Error::custom(format!(
+                        "unexpected method {:?}",
+                        other
+                    )));
+                }
+            });
+        }
+
+        match (
+            envelope.channel.as_deref(),
+            envelope.message_type.as_deref(),
+        ) {
+            (Some("heartbeat"), _) => Ok(KrakenMessage::Heartbeat),
+            (Some("status"), _) => {
+                let status: StatusMessage = serde_json::from_str(text)?;
+                Ok(KrakenMessage::Status(status.data))
+            }
+            (Some("level3"), Some("snapshot")) => {
+                Ok(KrakenMessage::Snapshot(serde_json::from_str(text)?))
+            }
+            (Some("level3"), Some("update")) => {
+                Ok(KrakenMessage::Update(serde_json::from_str(text)?))
+            }
+            (channel, message_type) => Err(serde_json::Error::custom(format!(
+                "unexpected message on channel {:?} with type {:?}",
+                channel, message_type
+            ))),
+        }
+    }
+}
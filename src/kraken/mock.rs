@@ -0,0 +1,403 @@
+//! A local stand-in for Kraken's `GetWebSocketsToken` REST call and v2 L3
+//! WebSocket, for tests that must run without a network.
+//!
+//! Every accepted WebSocket connection plays the next [`Script`] in order. A
+//! script waits for a valid `subscribe`, acks it like Kraken does and then
+//! streams its steps, which may include faults. Later `subscribe` and
+//! `unsubscribe` requests are answered as they arrive.
+
+use crate::client::Endpoints;
+use crate::messages::SubscribeRequest;
+use crate::subscription::VALID_DEPTHS;
+use crate::token::{Credentials, TOKEN_URL};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::json;
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, sleep_until};
+use tokio_tungstenite::tungstenite::{Message, Utf8Bytes};
+use tokio_tungstenite::{WebSocketStream, accept_async};
+
+pub const MOCK_API_KEY: &str = "mock-api-key";
+/// base64 of "mock-secret", the signer requires a valid base64 secret
+pub const MOCK_API_SECRET: &str = "bW9jay1zZWNyZXQ=";
+
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// Send a text frame as-is
+    Send(String),
+    /// Send a text frame that is not valid JSON
+    SendMalformed,
+    /// Pause before the next step
+    Delay(Duration),
+    /// Send a WebSocket ping
+    Ping,
+    /// Close the TCP connection without a close handshake
+    Drop,
+    /// Close the connection cleanly
+    Close,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Script {
+    /// Answer the subscribe with this error instead of acking it
+    pub reject: Option<String>,
+    pub steps: Vec<Step>,
+}
+
+impl Script {
+    pub fn new() -> Script {
+        Script::default()
+    }
+
+    /// Loads one frame per non-empty line of a JSON-lines fixture.
+    pub fn from_fixture(path: impl AsRef<Path>) -> io::Result<Script> {
+        let contents = std::fs::read_to_string(path)?;
+        let steps = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Step::Send(line.to_string()))
+            .collect();
+        Ok(Script {
+            reject: None,
+            steps,
+        })
+    }
+
+    pub fn step(mut self, step: Step) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    pub fn reject(mut self, error: impl Into<String>) -> Self {
+        self.reject = Some(error.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockConfig {
+    /// `expires` returned with every token
+    pub token_expires: u64,
+    /// Pairs the mock will accept subscriptions for
+    pub symbols: Vec<String>,
+    pub connections: Vec<Script>,
+}
+
+impl MockConfig {
+    pub fn connection(mut self, script: Script) -> Self {
+        self.connections.push(script);
+        self
+    }
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        MockConfig {
+            token_expires: 900,
+            symbols: ["ETH/BTC", "BTC/USD", "ETH/USD"].map(String::from).to_vec(),
+            connections: Vec::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    tokens: Vec<String>,
+    symbols: Vec<String>,
+    requests: Vec<SubscribeRequest>,
+    connections: usize,
+}
+
+pub struct MockKraken {
+    rest_addr: SocketAddr,
+    ws_addr: SocketAddr,
+    shared: Arc<Mutex<Shared>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl MockKraken {
+    pub async fn start(config: MockConfig) -> io::Result<MockKraken> {
+        let rest = TcpListener::bind("127.0.0.1:0").await?;
+        let ws = TcpListener::bind("127.0.0.1:0").await?;
+        let shared = Arc::new(Mutex::new(Shared {
+            symbols: config.symbols,
+            ..Shared::default()
+        }));
+
+        let rest_addr = rest.local_addr()?;
+        let ws_addr = ws.local_addr()?;
+
+        let rest_task = tokio::spawn(serve_rest(rest, shared.clone(), config.token_expires));
+        let ws_task = tokio::spawn(serve_ws(ws, shared.clone(), config.connections));
+
+        Ok(MockKraken {
+            rest_addr,
+            ws_addr,
+            shared,
+            tasks: vec![rest_task, ws_task],
+        })
+    }
+
+    pub fn endpoints(&self) -> Endpoints {
+        let path = TOKEN_URL
+            .strip_prefix("https://api.kraken.com")
+            .unwrap_or(TOKEN_URL);
+        Endpoints {
+            token_url: format!("http://{}{}", self.rest_addr, path),
+            ws_url: format!("ws://{}/v2", self.ws_addr),
+        }
+    }
+
+    pub fn credentials() -> Credentials {
+        Credentials::new(MOCK_API_KEY, MOCK_API_SECRET)
+    }
+
+    /// Stops accepting subscribe and unsubscribe requests for `symbol`.
+    pub fn delist(&self, symbol: &str) {
+        self.shared.lock().unwrap().symbols.retain(|s| s != symbol);
+    }
+
+    pub fn list(&self, symbol: impl Into<String>) {
+        self.shared.lock().unwrap().symbols.push(symbol.into());
+    }
+
+    /// Number of tokens handed out so far.
+    pub fn token_requests(&self) -> usize {
+        self.shared.lock().unwrap().tokens.len()
+    }
+
+    /// Number of WebSocket connections accepted so far.
+    pub fn connections(&self) -> usize {
+        self.shared.lock().unwrap().connections
+    }
+
+    /// Every subscribe and unsubscribe request received, in order.
+    pub fn requests(&self) -> Vec<SubscribeRequest> {
+        self.shared.lock().unwrap().requests.clone()
+    }
+}
+
+impl Drop for MockKraken {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+async fn serve_rest(listener: TcpListener, shared: Arc<Mutex<Shared>>, expires: u64) {
+    while let Ok((socket, _)) = listener.accept().await {
+        let shared = shared.clone();
+        tokio::spawn(async move {
+            let _ = handle_rest(socket, shared, expires).await;
+        });
+    }
+}
+
+async fn handle_rest(
+    mut socket: TcpStream,
+    shared: Arc<Mutex<Shared>>,
+    expires: u64,
+) -> io::Result<()> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    let header_end = loop {
+        let n = socket.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let content_length = head
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse::<usize>().ok())?
+        })
+        .unwrap_or(0);
+    while buf.len() < header_end + content_length {
+        let n = socket.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let has_key = head.lines().any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.eq_ignore_ascii_case("api-key") && value.trim() == MOCK_API_KEY
+        })
+    });
+    let has_sign = head
+        .lines()
+        .any(|line| line.to_ascii_lowercase().starts_with("api-sign:"));
+
+    let body = if !head.starts_with("POST /0/private/GetWebSocketsToken ") {
+        json!({ "error": ["EGeneral:Unknown method"] })
+    } else if !has_key || !has_sign {
+        json!({ "error": ["EAPI:Invalid key"] })
+    } else {
+        let mut shared = shared.lock().unwrap();
+        let token = format!("mock-token-{}", shared.tokens.len() + 1);
+        shared.tokens.push(token.clone());
+        json!({ "error": [], "result": { "token": token, "expires": expires } })
+    }
+    .to_string();
+
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    socket.write_all(response.as_bytes()).await?;
+    socket.shutdown().await
+}
+
+async fn serve_ws(listener: TcpListener, shared: Arc<Mutex<Shared>>, scripts: Vec<Script>) {
+    let mut scripts = VecDeque::from(scripts);
+    while let Ok((socket, _)) = listener.accept().await {
+        shared.lock().unwrap().connections += 1;
+        let script = scripts.pop_front().unwrap_or_default();
+        let shared = shared.clone();
+        tokio::spawn(async move {
+            if let Ok(ws) = accept_async(socket).await {
+                let _ = run_script(ws, shared, script).await;
+            }
+        });
+    }
+}
+
+async fn run_script(
+    mut ws: WebSocketStream<TcpStream>,
+    shared: Arc<Mutex<Shared>>,
+    script: Script,
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    let mut steps = VecDeque::from(script.steps);
+    let mut subscribed = false;
+    let mut next_at = Instant::now();
+
+    loop {
+        tokio::select! {
+            incoming = ws.next() => {
+                let text = match incoming {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return Ok(()),
+                    Some(Ok(_)) => continue,
+                };
+                let (responses, accepted) = handle_request(&text, &shared, script.reject.as_deref());
+                for response in responses {
+                    ws.send(text_frame(response.to_string())).await?;
+                }
+                if accepted && !subscribed {
+                    subscribed = true;
+                    next_at = Instant::now();
+                }
+            }
+            _ = sleep_until(next_at), if subscribed && !steps.is_empty() => {
+                match steps.pop_front() {
+                    Some(Step::Send(text)) => ws.send(text_frame(text)).await?,
+                    Some(Step::SendMalformed) => {
+                        ws.send(text_frame(r#"{"channel":"level3","type":"update","data":["#.to_string())).await?
+                    }
+                    Some(Step::Delay(delay)) => next_at = Instant::now() + delay,
+                    Some(Step::Ping) => ws.send(Message::Ping(Default::default())).await?,
+                    Some(Step::Drop) => return Ok(()),
+                    Some(Step::Close) => return ws.close(None).await,
+                    None => {}
+                }
+            }
+        }
+    }
+}
+
+/// Validates a client request and builds Kraken's per-symbol method
+/// responses. Also returns whether any symbol was subscribed.
+fn handle_request(
+    text: &str,
+    shared: &Mutex<Shared>,
+    reject: Option<&str>,
+) -> (Vec<serde_json::Value>, bool) {
+    let request: SubscribeRequest = match serde_json::from_str(text) {
+        Ok(request) => request,
+        Err(_) => {
+            let error = json!({ "error": "EGeneral:Invalid arguments", "success": false });
+            return (vec![error], false);
+        }
+    };
+
+    let mut shared = shared.lock().unwrap();
+    let params = &request.params;
+    let subscribing = request.method == "subscribe";
+    let error = if let Some(reject) = reject {
+        Some(reject.to_string())
+    } else if !subscribing && request.method != "unsubscribe" {
+        Some("EGeneral:Invalid arguments:method".to_string())
+    } else if params.channel != "level3" {
+        Some("EGeneral:Invalid arguments:channel".to_string())
+    } else if !shared.tokens.contains(&params.token) {
+        Some("EAPI:Invalid token".to_string())
+    } else if subscribing
+        && params
+            .depth
+            .is_some_and(|depth| !VALID_DEPTHS.contains(&depth))
+    {
+        Some("EGeneral:Invalid arguments:depth".to_string())
+    } else {
+        None
+    };
+
+    let mut accepted = false;
+    let responses = params
+        .symbol
+        .iter()
+        .map(|symbol| {
+            let error = error.clone().or_else(|| {
+                (!shared.symbols.contains(symbol))
+                    .then(|| "Currency pair not supported".to_string())
+            });
+            accepted |= subscribing && error.is_none();
+            let mut response = match &error {
+                Some(error) => json!({
+                    "method": request.method,
+                    "success": false,
+                    "error": error,
+                    "symbol": symbol,
+                }),
+                None => json!({
+                    "method": request.method,
+                    "success": true,
+                    "result": {
+                        "channel": params.channel,
+                        "symbol": symbol,
+                        "snapshot": params.snapshot.unwrap_or(true),
+                    },
+                }),
+            };
+            if let Some(req_id) = params.req_id {
+                response["req_id"] = json!(req_id);
+            }
+            response
+        })
+        .collect();
+
+    shared.requests.push(request);
+    (responses, accepted)
+}
+
+fn text_frame(text: String) -> Message {
+    Message::Text(Utf8Bytes::from(text))
+}
@@ -1,3 +1,10 @@
 pub mod adapter;
+pub mod checksum;
 pub mod client;
+pub mod decimal;
+pub mod instrument;
 pub mod messages;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod subscription;
+pub mod token;
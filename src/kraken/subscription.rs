@@ -0,0 +1,108 @@
+use crate::client::ConnectionError;
+use crate::messages::SubscribeParams;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// The only channel the adapter knows how to mirror.
+pub const LEVEL3_CHANNEL: &str = "level3";
+
+/// Book depths accepted by Kraken's level3 channel.
+pub const VALID_DEPTHS: [u32; 3] = [10, 100, 1000];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubscriptionConfig {
+    pub channel: String,
+    pub symbols: Vec<String>,
+    pub depth: u32,
+    pub snapshot: bool,
+}
+
+impl SubscriptionConfig {
+    /// Checks the subscription locally so a bad config never reaches Kraken.
+    pub fn validate(&self) -> Result<(), ConnectionError> {
+        if self.channel != LEVEL3_CHANNEL {
+            return Err(ConnectionError::InvalidSubscription(format!(
+                "unsupported channel {:?}",
+                self.channel
+            )));
+        }
+
+        if self.symbols.is_empty() {
+            return Err(ConnectionError::InvalidSubscription(
+                "no symbols given".to_string(),
+            ));
+        }
+
+        let mut seen = HashSet::new();
+        for symbol in &self.symbols {
+            let valid = symbol
+                .split_once('/')
+                .is_some_and(|(base, quote)| !base.is_empty() && !quote.is_empty())
+                && symbol.matches('/').count() == 1;
+            if !valid {
+                return Err(ConnectionError::InvalidSubscription(format!(
+                    "symbol {:?} is not of the form BASE/QUOTE",
+                    symbol
+                )));
+            }
+            if !seen.insert(symbol) {
+                return Err(ConnectionError::InvalidSubscription(format!(
+                    "duplicate symbol {:?}",
+                    symbol
+                )));
+            }
+        }
+
+        if !VALID_DEPTHS.contains(&self.depth) {
+            return Err(ConnectionError::InvalidSubscription(format!(
+                "depth {} is not one of {:?}",
+                self.depth, VALID_DEPTHS
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub fn subscribe_params(
+        &self,
+        symbols: Vec<String>,
+        token: String,
+        req_id: u64,
+    ) -> SubscribeParams {
+        SubscribeParams {
+            channel: self.channel.clone(),
+            symbol: symbols,
+            depth: Some(self.depth),
+            snapshot: Some(self.snapshot),
+            token,
+            req_id: Some(req_id),
+        }
+    }
+
+    pub fn unsubscribe_params(
+        &self,
+        symbols: Vec<String>,
+        token: String,
+        req_id: u64,
+    ) -> SubscribeParams {
+        SubscribeParams {
+            channel: self.channel.clone(),
+            symbol: symbols,
+            depth: None,
+            snapshot: None,
+            token,
+            req_id: Some(req_id),
+        }
+    }
+}
+
+impl Default for SubscriptionConfig {
+    fn default() -> Self {
+        SubscriptionConfig {
+            channel: LEVEL3_CHANNEL.to_string(),
+            symbols: vec!["ETH/BTC".to_string()],
+            depth: 1000,
+            snapshot: true,
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use crate::client::ConnectionError;
+use crate::messages::{TokenResponse, TokenResponseResult};
+use base64::{Engine as _, engine::general_purpose};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512};
+use std::env;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+type HmacSha512 = Hmac<Sha512>;
+
+pub const TOKEN_URL: &str = "https://api.kraken.com/0/private/GetWebSocketsToken";
+
+#[derive(Clone)]
+pub struct Credentials {
+    pub api_key: String,
+    pub api_secret: String,
+}
+
+impl Credentials {
+    pub fn new(api_key: impl Into<String>, api_secret: impl Into<String>) -> Credentials {
+        Credentials {
+            api_key: api_key.into(),
+            api_secret: api_secret.into(),
+        }
+    }
+
+    /// Reads `KRAKEN_API_KEY` and `KRAKEN_PRIVATE_KEY`, loading `.env` if present.
+    pub fn from_env() -> Result<Credentials, ConnectionError> {
+        dotenvy::dotenv().ok();
+        let api_key = env::var("KRAKEN_API_KEY").map_err(|_| ConnectionError::MissingApiKey)?;
+        let api_secret =
+            env::var("KRAKEN_PRIVATE_KEY").map_err(|_| ConnectionError::MissingApiKey)?;
+        Ok(Credentials::new(api_key, api_secret))
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("api_key", &self.api_key)
+            .field("api_secret", &"<redacted>")
+            .finish()
+    }
+}
+
+/// A WebSocket token together with when it stops being accepted for subscribing.
+#[derive(Debug, Clone)]
+pub struct WsToken {
+    pub value: String,
+    pub issued_at: Instant,
+    pub expires_in: Duration,
+}
+
+impl WsToken {
+    pub fn remaining(&self) -> Duration {
+        self.expires_in.saturating_sub(self.issued_at.elapsed())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+}
+
+/// Owns the API credentials and hands out tokens that are still valid,
+/// fetching a replacement `refresh_margin` before the current one expires.
+pub struct TokenManager {
+    credentials: Credentials,
+    url: String,
+    /// Path component of `url`, which is part of the signed message
+    path: String,
+    refresh_margin: Duration,
+    current: Option<WsToken>,
+}
+
+impl TokenManager {
+    pub fn new(credentials: Credentials, url: &str) -> Result<TokenManager, ConnectionError> {
+        let uri: http::Uri = url
+            .parse()
+            .map_err(|_| ConnectionError::InvalidEndpoint(url.to_string()))?;
+        if !matches!(uri.scheme_str(), Some("http" | "https")) {
+            return Err(ConnectionError::InvalidEndpoint(url.to_string()));
+        }
+
+        Ok(TokenManager {
+            credentials,
+            url: url.to_string(),
+            path: uri.path().to_string(),
+            refresh_margin: Duration::from_secs(60),
+            current: None,
+        })
+    }
+
+    pub fn with_refresh_margin(mut self, margin: Duration) -> Self {
+        self.refresh_margin = margin;
+        self
+    }
+
+    pub fn current(&self) -> Option<&WsToken> {
+        self.current.as_ref()
+    }
+
+    /// True when there is no token or the current one is inside the refresh margin.
+    pub fn needs_refresh(&self) -> bool {
+        self.current
+            .as_ref()
+            .is_none_or(|token| token.remaining() <= self.refresh_margin)
+    }
+
+    /// Forgets the current token so the next call to [`TokenManager::token`] fetches a new one.
+    pub fn invalidate(&mut self) {
+        self.current = None;
+    }
+
+    /// Returns a token that is safe to subscribe with, refreshing it if due.
+    pub async fn token(&mut self) -> Result<WsToken, ConnectionError> {
+        if self.needs_refresh() {
+            self.refresh().await?;
+        }
+        match &self.current {
+            Some(token) if !token.is_expired() => Ok(token.clone()),
+            _ => Err(ConnectionError::TokenExpired),
+        }
+    }
+
+    pub async fn refresh(&mut self) -> Result<&WsToken, ConnectionError> {
+        let nonce = chrono::Utc::now().timestamp_millis().to_string();
+        let postdata = format!("nonce={}", nonce);
+        let api_sign =
+            Self::kraken_api_sign(&self.path, &nonce, &postdata, &self.credentials.api_secret)?;
+
+        let issued_at = Instant::now();
+        let result = Self::get_ws_token(
+            &self.url,
+            self.credentials.api_key.clone(),
+            api_sign,
+            postdata,
+        )
+        .await?;
+
+        Ok(self.current.insert(WsToken {
+            value: result.token,
+            issued_at,
+            expires_in: Duration::from_secs(result.expires),
+        }))
+    }
+
+    fn kraken_api_sign(
+        path: &str,
+        nonce: &str,
+        postdata: &str,
+        api_secret: &str,
+    ) -> Result<String, ConnectionError> {
+        // 1. SHA256(nonce + postdata)
+        let mut sha256 = Sha256::new();
+        sha256.update(nonce.as_bytes());
+        sha256.update(postdata.as_bytes());
+        let hash = sha256.finalize();
+
+        // 2. path + hash
+        let mut message = Vec::with_capacity(path.len() + hash.len());
+        message.extend_from_slice(path.as_bytes());
+        message.extend_from_slice(&hash);
+
+        // 3. HMAC-SHA512
+        let secret = general_purpose::STANDARD
+            .decode(api_secret)
+            .map_err(|_| ConnectionError::InvalidApiSecret)?;
+
+        let mut mac = HmacSha512::new_from_slice(&secret).expect("HMAC can take key of any size");
+        mac.update(&message);
+
+        // 4. base64 encode
+        Ok(general_purpose::STANDARD.encode(mac.finalize().into_bytes()))
+    }
+
+    async fn get_ws_token(
+        url: &str,
+        api_key: String,
+        api_sign: String,
+        postdata: String,
+    ) -> Result<TokenResponseResult, ConnectionError> {
+        let client = reqwest::Client::new();
+        let response = client
+            .post(url)
+            .header("API-Key", api_key)
+            .header("API-Sign", api_sign)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .header("Accept", "application/json")
+            .body(postdata)
+            .send()
+            .await?;
+
+        let token: TokenResponse = response
+            .json()
+            .await
+            .map_err(ConnectionError::InvalidRequest)?;
+
+        if !token.error.is_empty() {
+            return Err(ConnectionError::ApiError(token.error.join(", ")));
+        }
+        token.result.ok_or(ConnectionError::MissingToken)
+    }
+}
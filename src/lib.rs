@@ -1,5 +1,8 @@
 pub mod benchmarking;
+pub mod coinbase;
+pub mod feed;
 pub mod kraken;
 
 pub use benchmarking::*;
+pub use feed::*;
 pub use kraken::*;
@@ -1,13 +1,21 @@
 use battery::{Manager, State};
 use chrono::prelude::*;
-use matchbook::orderbook::Orderbook;
-use matchstick::adapter::ParseError;
-use matchstick::kraken::client::KrakenClient;
-use matchstick::messages::{Data, Response};
-use std::fs::{self, create_dir_all};
+use matchstick::adapter::KrakenAdapter;
+use matchstick::audit::AuditLog;
+use matchstick::book::{BookSet, BookSide};
+use matchstick::coinbase::adapter::{ReplaySummary, replay_lines};
+use matchstick::harness::{HarnessReport, ReplayHarness};
+use matchstick::kraken::client::{ConnectionError, FeedEvent, KrakenClient};
+use matchstick::messages::KrakenMessage;
+use matchstick::query::CaptureQuery;
+use matchstick::recorder::{CaptureHeader, CaptureInfo, Recorder};
+use matchstick::stats::{ConnectionStats, QualityStats, SymbolLatencyStats, TimingStats};
+use std::fs::{self, File, create_dir_all};
+use std::io::BufReader;
 use std::process::{Command, Stdio, exit};
+use std::time::Duration;
 use sysinfo::System;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use tracing_subscriber::{EnvFilter, fmt};
 
 fn init_logging() {
@@ -19,55 +27,26 @@ fn init_logging() {
         .init();
 }
 
-pub fn process_message(orderbook: &mut Orderbook, message: Response) -> Result<(), ParseError> {
-    if message.channel != "level3" {
-        return Err(ParseError::InvalidChannel);
-    }
-    if message.message_type != "update" {
-        return Err(ParseError::InvalidType);
-    }
-    if message.data.is_empty() {
-        return Err(ParseError::Empty);
-    }
+// Directory to save benchmark reports
+const REPORT_DIR: &str = "reports";
 
-    for data in &message.data {
-        // Process bids
-        for event in &data.bids {
-            process_event(orderbook, event, true)?;
-        }
-        // Process asks
-        for event in &data.asks {
-            process_event(orderbook, event, false)?;
-        }
-    }
+// Pairs mirrored side by side during the live run
+const LIVE_SYMBOLS: [&str; 3] = ["BTC/USD", "ETH/USD", "ETH/BTC"];
 
-    Ok(())
-}
+// Raw frames of the live run, written to the report directory for replay
+const LIVE_CAPTURE: &str = "kraken.capture";
 
-fn process_event(
-    orderbook: &mut Orderbook,
-    event: &OrderEvent,
-    is_bid: bool,
-) -> Result<(), ParseError> {
-    match event.event {
-        EventType::Add => {
-            let order = to_order(event, is_bid);
-            orderbook.add(order)?;
-        }
-        EventType::Modify => {
-            let id = to_order_id(&event.order_id);
-            let qty = to_quantity(event.order_qty);
-            orderbook.modify_order(id, qty)?;
-        }
-        EventType::Delete => {
-            let id = to_order_id(&event.order_id);
-            orderbook.cancel_order(id)?;
-        }
-    }
-    Ok(())
-}
-// Directory to save benchmark reports
-const REPORT_DIR: &str = "reports";
+// Path to a recorded Coinbase full channel capture to replay, if any
+const COINBASE_CAPTURE_ENV: &str = "COINBASE_CAPTURE";
+
+// Wait between attempts to resubscribe a symbol after a failed one
+const RESYNC_RETRY: Duration = Duration::from_secs(5);
+
+// Longest silence between a symbol's updates before it counts as a feed gap
+const GAP_THRESHOLD: Duration = Duration::from_secs(5);
+
+// Recorded time between book checkpoints in captures
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
 
 // Check if running on AC power
 fn on_ac_power() -> bool {
@@ -266,52 +245,631 @@ fn run_perf_stat() {
     info!("perf stat not supported on this OS, skipping");
 }
 
+/// Results of the live latency run
+struct LiveReport {
+    latency: SymbolLatencyStats,
+    connection: ConnectionStats,
+    quality: QualityStats,
+    timing: TimingStats,
+}
+
 /// Live latency measurements (custom)
-async fn run_live_latency() -> Result<LatencyStats, Box<dyn std::error::Error>> {
+// `resubscribe` fetches a fresh snapshot for a book that fails its checksum
+async fn run_live_latency(resubscribe: bool) -> Result<LiveReport, Box<dyn std::error::Error>> {
     info!("Running live latency benchmarks...");
     dotenvy::dotenv().ok();
 
-    let mut client = KrakenClient::new().await?;
-    let mut orderbook = Orderbook::new();
-    let mut stats = LatencyStats::new();
-
-    let duration = std::time::Duration::from_secs(60);
+    create_dir_all(REPORT_DIR)?;
+    let mut client = KrakenClient::builder()
+        .symbols(LIVE_SYMBOLS)
+        .record_to(format!("{}/{}", REPORT_DIR, LIVE_CAPTURE))
+        .connect()
+        .await?;
+    let mut books = BookSet::with_symbols(LIVE_SYMBOLS);
+    books.audit_to(AuditLog::create(format!("{}/audit.jsonl", REPORT_DIR))?);
+    let mut adapter = KrakenAdapter::with_gap_threshold(GAP_THRESHOLD);
+    let mut stats = SymbolLatencyStats::new();
+    let mut quality = QualityStats::default();
+
+    let duration = Duration::from_secs(60);
     let start = std::time::Instant::now();
+    let mut last_checkpoint = start;
+    let mut last_resync = start;
 
     while start.elapsed() < duration {
-        let msg = client.read().await?;
+        if !client.pending_resyncs().is_empty() && last_resync.elapsed() >= RESYNC_RETRY {
+            last_resync = std::time::Instant::now();
+            if let Err(e) = client.retry_resyncs().await {
+                warn!("Resubscribe retry failed: {}", e);
+            }
+        }
+
+        // A reconnect backing off must not hold the run past its duration
+        let remaining = duration.saturating_sub(start.elapsed());
+        let Ok(event) = tokio::time::timeout(remaining, client.next_event()).await else {
+            warn!("Run ended while waiting on the Kraken connection");
+            break;
+        };
+        let update = match event {
+            Ok(FeedEvent::Message(KrakenMessage::Snapshot(response)))
+            | Ok(FeedEvent::Message(KrakenMessage::Update(response))) => response,
+            Ok(FeedEvent::Message(KrakenMessage::MethodError(response))) => {
+                warn!("Kraken rejected {}: {:?}", response.method, response.error);
+                continue;
+            }
+            Ok(FeedEvent::Message(_)) => continue,
+            Ok(FeedEvent::Resync) => {
+                // Wait for the snapshot sent on the new connection
+                books.reset();
+                continue;
+            }
+            Err(ConnectionError::Malformed(e)) => {
+                warn!("Skipping malformed message: {}", e);
+                continue;
+            }
+            Err(e) => {
+                // Keep what was measured before the connection gave out
+                error!("Live run stopped early: {}", e);
+                break;
+            }
+        };
 
-        if let Ok(update) = serde_json::from_str::<Update>(&msg) {
-            let op_start = std::time::Instant::now();
-            let result = process_update(&mut orderbook, update);
-            let elapsed = op_start.elapsed();
+        let result = adapter.process(&mut books, &update, |event| stats.record(&event));
+
+        let mismatches = match result {
+            Ok(report) => {
+                quality.record_report(&report);
+                report.mismatches
+            }
+            Err(e) => {
+                quality.record_error(&e);
+                Vec::new()
+            }
+        };
+        for mismatch in &mismatches {
+            warn!("{}", mismatch);
+            if resubscribe {
+                // Drop updates still in flight until the fresh snapshot lands
+                books.reset_symbol(&mismatch.symbol);
+                quality.resubscribes += 1;
+                if let Err(e) = client.resubscribe_symbol(&mismatch.symbol).await {
+                    // The book stays unsynced until a retry succeeds
+                    warn!(
+                        "Resubscribing to {} failed, will retry: {}",
+                        mismatch.symbol, e
+                    );
+                    last_resync = std::time::Instant::now();
+                }
+            }
+        }
 
-            match result {
-                Ok(()) => stats.record_add(elapsed), // TODO: distinguish add/modify/delete
-                Err(_) => {}
+        // Only checkpoint once every recorded frame has been applied
+        if last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL && client.pending_messages() == 0 {
+            let connection = client.connection_id();
+            if let Some(recorder) = client.recorder_mut() {
+                recorder.record_checkpoint(connection, &books.checkpoint())?;
+                last_checkpoint = std::time::Instant::now();
             }
         }
     }
 
-    info!("Processed messages for {:?}", duration);
-    Ok(stats)
+    if let Some(log) = books.audit_log_mut() {
+        log.flush()?;
+        info!("Audit log: {} records", log.records());
+    }
+    if let Some(recorder) = client.take_recorder() {
+        info!(
+            "Captured {} records to {}",
+            recorder.records(),
+            LIVE_CAPTURE
+        );
+        recorder.finish()?;
+    }
+
+    info!("Processed messages for {:?}", start.elapsed().min(duration));
+    Ok(LiveReport {
+        latency: stats,
+        connection: client.connection_stats().clone(),
+        quality,
+        timing: adapter.clock().stats().clone(),
+    })
+}
+
+/// Replay the live run's capture through matchbook as fast as possible (custom)
+fn run_capture_replay() -> Result<Option<HarnessReport>, Box<dyn std::error::Error>> {
+    let path = format!("{}/{}", REPORT_DIR, LIVE_CAPTURE);
+    if !std::path::Path::new(&path).exists() {
+        info!("No capture at {}, skipping capture replay", path);
+        return Ok(None);
+    }
+    info!("Replaying capture {}...", path);
+
+    let report = ReplayHarness::open(&path)?.run()?;
+    info!(
+        "Replayed {} records in {:?}",
+        report.records, report.elapsed
+    );
+    Ok(Some(report))
+}
+
+/// Results of replaying a Coinbase capture
+struct ReplayReport {
+    latency: SymbolLatencyStats,
+    summary: ReplaySummary,
+}
+
+/// Replay a recorded Coinbase capture through matchbook (custom)
+fn run_coinbase_replay() -> Result<Option<ReplayReport>, Box<dyn std::error::Error>> {
+    let Ok(path) = std::env::var(COINBASE_CAPTURE_ENV) else {
+        info!("{} not set, skipping Coinbase replay", COINBASE_CAPTURE_ENV);
+        return Ok(None);
+    };
+    info!("Replaying Coinbase capture {}...", path);
+
+    let mut books = BookSet::new();
+    let mut stats = SymbolLatencyStats::new();
+    let reader = BufReader::new(File::open(&path)?);
+    let summary = replay_lines(reader, &mut books, |event| stats.record(&event))?;
+
+    info!(
+        "Replayed {} lines, {} events applied",
+        summary.lines, summary.applied
+    );
+    Ok(Some(ReplayReport {
+        latency: stats,
+        summary,
+    }))
+}
+
+fn latency_table(title: &str, latency: &SymbolLatencyStats) -> String {
+    let mut table = format!(
+        "\n## {} (ns)\n\n| Symbol | Event | p50 | p95 | p99 |\n|---|---|---|---|---|\n",
+        title
+    );
+    for (symbol, stats) in latency.iter() {
+        let stats = stats.get_stats();
+        let events = [
+            ("Add", stats.add_order),
+            ("Modify", stats.modify_order),
+            ("Delete", stats.cancel_order),
+        ];
+        for (event, report) in events {
+            table.push_str(&format!(
+                "| {} | {} | {} | {} | {} |\n",
+                symbol, event, report.p50, report.p95, report.p99
+            ));
+        }
+    }
+    table
 }
 
 // Generate summary
-fn generate_summary() {
+fn generate_summary(
+    live: Option<&LiveReport>,
+    capture: Option<&HarnessReport>,
+    replay: Option<&ReplayReport>,
+) {
+    let mut summary = String::from("# Benchmark Summary\n\nResults go here\n");
+    if let Some(live) = live {
+        summary.push_str(&latency_table("Live Latency", &live.latency));
+
+        summary.push_str("\n## Live Connection\n\n| Metric | Value |\n|---|---|\n");
+        summary.push_str(&format!(
+            "| Reconnects | {} |\n",
+            live.connection.reconnects
+        ));
+        summary.push_str(&format!(
+            "| Stale book time | {:?} |\n",
+            live.connection.stale
+        ));
+
+        summary.push_str("\n## Feed Quality\n\n| Metric | Value |\n|---|---|\n");
+        summary.push_str(&format!(
+            "| Checksum mismatches | {} |\n",
+            live.quality.checksum_mismatches
+        ));
+        summary.push_str(&format!(
+            "| Resubscribes | {} |\n",
+            live.quality.resubscribes
+        ));
+        summary.push_str(&format!(
+            "| Invalid prices/quantities | {} |\n",
+            live.quality.invalid_values
+        ));
+        summary.push_str(&format!(
+            "| Updates before snapshot | {} |\n",
+            live.quality.unsynced_updates
+        ));
+        summary.push_str(&format!(
+            "| Rejected events | {} |\n",
+            live.quality.rejected_events
+        ));
+        summary.push_str(&format!("| Anomalies | {} |\n", live.quality.anomalies));
+
+        let latency = live.timing.latency();
+        summary.push_str("\n## Feed Timing\n\n| Metric | Value |\n|---|---|\n");
+        summary.push_str(&format!(
+            "| Out-of-order updates | {} |\n",
+            live.timing.out_of_order
+        ));
+        summary.push_str(&format!(
+            "| Gaps over {:?} | {} |\n",
+            GAP_THRESHOLD, live.timing.gaps
+        ));
+        summary.push_str(&format!(
+            "| Largest gap | {:?} |\n",
+            live.timing.largest_gap
+        ));
+        for (percentile, nanos) in [
+            ("p50", latency.p50),
+            ("p95", latency.p95),
+            ("p99", latency.p99),
+        ] {
+            summary.push_str(&format!(
+                "| Exchange to local latency {} | {:?} |\n",
+                percentile,
+                Duration::from_nanos(nanos)
+            ));
+        }
+    }
+
+    if let Some(capture) = capture {
+        summary.push_str(&latency_table("Capture Replay Latency", &capture.latency));
+
+        summary.push_str("\n## Capture Replay\n\n| Metric | Value |\n|---|---|\n");
+        summary.push_str(&format!("| Records | {} |\n", capture.records));
+        summary.push_str(&format!("| Malformed frames | {} |\n", capture.malformed));
+        summary.push_str(&format!("| Messages failed | {} |\n", capture.failed));
+        summary.push_str(&format!(
+            "| Checksum mismatches | {} |\n",
+            capture.quality.checksum_mismatches
+        ));
+        summary.push_str(&format!(
+            "| Rejected events | {} |\n",
+            capture.quality.rejected_events
+        ));
+        summary.push_str(&format!("| Anomalies | {} |\n", capture.quality.anomalies));
+        summary.push_str(&format!(
+            "| Checkpoints (mismatched) | {} ({}) |\n",
+            capture.checkpoints, capture.checkpoint_mismatches
+        ));
+        summary.push_str(&format!("| Replay time | {:?} |\n", capture.elapsed));
+    }
+
+    if let Some(replay) = replay {
+        summary.push_str(&latency_table("Coinbase Replay Latency", &replay.latency));
+
+        summary.push_str("\n## Coinbase Replay\n\n| Metric | Value |\n|---|---|\n");
+        summary.push_str(&format!("| Lines | {} |\n", replay.summary.lines));
+        summary.push_str(&format!("| Malformed | {} |\n", replay.summary.malformed));
+        summary.push_str(&format!(
+            "| Events applied | {} |\n",
+            replay.summary.applied
+        ));
+        summary.push_str(&format!(
+            "| Events skipped | {} |\n",
+            replay.summary.skipped
+        ));
+        summary.push_str(&format!(
+            "| Messages failed | {} |\n",
+            replay.summary.failed
+        ));
+        summary.push_str(&format!("| Anomalies | {} |\n", replay.summary.anomalies));
+    }
+
     create_dir_all(REPORT_DIR).expect("Failed to create report dir");
-    fs::write(
-        format!("{}/SUMMARY.md", REPORT_DIR),
-        "# Benchmark Summary\n\nResults go here",
-    )
-    .expect("Failed to write summary");
+    fs::write(format!("{}/SUMMARY.md", REPORT_DIR), summary).expect("Failed to write summary");
     info!("Results saved to: {}", REPORT_DIR);
     info!("View summary: cat {}/SUMMARY.md", REPORT_DIR);
 }
 
+// Command line: the benchmark suite, or tools for recorded captures
+fn cli() -> clap::Command {
+    clap::Command::new("matchstick")
+        .about("Benchmarks matchbook against live and recorded L3 feeds")
+        .subcommand(
+            clap::Command::new("benchmark")
+                .about("Run the full benchmark suite (default)")
+                .arg(
+                    clap::Arg::new("no-resubscribe")
+                        .long("no-resubscribe")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Keep a book that fails its checksum instead of resubscribing"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("capture")
+                .about("Inspect recorded feed captures")
+                .subcommand_required(true)
+                .subcommand(
+                    clap::Command::new("info")
+                        .about("Print the message counts, time span and symbols of a capture")
+                        .arg(clap::Arg::new("path").required(true)),
+                )
+                .subcommand(
+                    clap::Command::new("checkpoint")
+                        .about("Copy a capture, adding book checkpoints for fast seeking")
+                        .arg(clap::Arg::new("input").required(true))
+                        .arg(clap::Arg::new("output").required(true))
+                        .arg(
+                            clap::Arg::new("every")
+                                .long("every")
+                                .value_name("SECS")
+                                .value_parser(clap::value_parser!(u64))
+                                .default_value("10")
+                                .help("Recorded seconds between checkpoints"),
+                        ),
+                )
+                .subcommand(
+                    clap::Command::new("book")
+                        .about("Print a symbol's price levels as they stood at a time")
+                        .arg(clap::Arg::new("path").required(true))
+                        .arg(clap::Arg::new("symbol").required(true))
+                        .arg(time_arg("at").required(true)),
+                )
+                .subcommand(
+                    clap::Command::new("diff")
+                        .about("Print the orders added, removed and resized between two times")
+                        .arg(clap::Arg::new("path").required(true))
+                        .arg(clap::Arg::new("symbol").required(true))
+                        .arg(time_arg("from").required(true))
+                        .arg(time_arg("to").required(true))
+                        .arg(
+                            clap::Arg::new("json")
+                                .long("json")
+                                .action(clap::ArgAction::SetTrue)
+                                .help("Print the diff as JSON"),
+                        ),
+                ),
+        )
+}
+
+fn time_arg(name: &'static str) -> clap::Arg {
+    clap::Arg::new(name)
+        .long(name)
+        .value_name("TIME")
+        .help("RFC 3339 timestamp, or a UTC time of day on the day the capture started")
+}
+
+// Read a query time, e.g. 2025-06-01T12:03:17.412Z or 12:03:17.412
+fn parse_time(value: &str, header: &CaptureHeader) -> Result<DateTime<Utc>, String> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Ok(at.with_timezone(&Utc));
+    }
+    NaiveTime::parse_from_str(value, "%H:%M:%S%.f")
+        .map(|time| header.started_at.date_naive().and_time(time).and_utc())
+        .map_err(|_| {
+            format!(
+                "{:?} is neither an RFC 3339 timestamp nor a time of day",
+                value
+            )
+        })
+}
+
+// Print the price levels of a symbol at a time in a capture
+fn print_capture_book(
+    path: &str,
+    symbol: &str,
+    at: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let query = CaptureQuery::open(path)?;
+    let at = parse_time(at, query.header())?;
+    let book = query.book_at(symbol, at)?;
+
+    println!("{} at {}", symbol, at.to_rfc3339());
+    if !book.is_synced() {
+        println!("Warning: no snapshot had been received by then");
+    }
+    let spec = book.spec;
+    let levels = book.orderbook.get_levels();
+    println!("Orderbook levels:");
+    let sides = [("Asks", &levels.asks), ("Bids", &levels.bids)];
+    for (title, levels) in sides {
+        println!("  {}:", title);
+        println!("    {:>20} {:>20}", "price", "qty");
+        for &(price, qty) in levels {
+            println!(
+                "    {:>20} {:>20}",
+                spec.format_price(price),
+                spec.format_qty(qty)
+            );
+        }
+    }
+
+    // The index also holds orders parked out of matchbook's reach
+    if !book.parked().is_empty() {
+        println!("Index levels, including parked orders:");
+        let sides: [(&str, BookSide, Vec<_>); 2] = [
+            ("Asks", BookSide::Ask, book.orders.asks().collect()),
+            ("Bids", BookSide::Bid, book.orders.bids().collect()),
+        ];
+        for (title, side, levels) in sides {
+            println!("  {}:", title);
+            println!(
+                "    {:>20} {:>20} {:>8} {:>8}",
+                "price", "qty", "orders", "parked"
+            );
+            for (price, ids) in levels {
+                let qty: u64 = ids
+                    .iter()
+                    .filter_map(|id| book.orders.get(id))
+                    .map(|order| order.qty)
+                    .sum();
+                println!(
+                    "    {:>20} {:>20} {:>8} {:>8}",
+                    spec.format_price(price),
+                    spec.format_qty(qty),
+                    ids.len(),
+                    book.parked().count_at(side, price)
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+// Print how a symbol's orders changed between two times in a capture
+fn print_capture_diff(
+    path: &str,
+    symbol: &str,
+    from: &str,
+    to: &str,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let query = CaptureQuery::open(path)?;
+    let from = parse_time(from, query.header())?;
+    let to = parse_time(to, query.header())?;
+    let diff = query.diff(symbol, from, to)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+        return Ok(());
+    }
+
+    let spec = query
+        .header()
+        .instrument(symbol)
+        .map(|instrument| instrument.spec())
+        .unwrap_or_default();
+    println!(
+        "{} from {} to {}",
+        symbol,
+        from.to_rfc3339(),
+        to.to_rfc3339()
+    );
+    println!(
+        "{} added, {} removed, {} resized",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.resized.len()
+    );
+    let sections = [("Added", &diff.added), ("Removed", &diff.removed)];
+    for (title, orders) in sections {
+        if orders.is_empty() {
+            continue;
+        }
+        println!("{}:", title);
+        for order in orders {
+            println!(
+                "  {:<24} {:?} {} x {}",
+                order.order_id,
+                order.side,
+                spec.format_price(order.price),
+                spec.format_qty(order.qty)
+            );
+        }
+    }
+    if !diff.resized.is_empty() {
+        println!("Resized:");
+        for order in &diff.resized {
+            println!(
+                "  {:<24} {:?} {} x {} -> {}",
+                order.order_id,
+                order.side,
+                spec.format_price(order.price),
+                spec.format_qty(order.before),
+                spec.format_qty(order.after)
+            );
+        }
+    }
+    Ok(())
+}
+
+// Print what a capture holds
+fn print_capture_info(path: &str) {
+    let info = match CaptureInfo::read(path) {
+        Ok(info) => info,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path, e);
+            exit(1);
+        }
+    };
+
+    println!("Capture:     {}", path);
+    println!("Version:     {}", info.version);
+    println!("Venue:       {}", info.venue);
+    let symbols: Vec<&str> = info.symbols.iter().map(String::as_str).collect();
+    println!("Symbols:     {}", symbols.join(", "));
+    println!("Records:     {}", info.records);
+    if let (Some(first), Some(last)) = (info.first, info.last) {
+        println!("From:        {}", first.to_rfc3339());
+        println!("To:          {}", last.to_rfc3339());
+        println!("Span:        {:?}", info.span());
+    }
+    println!("Disconnects: {}", info.disconnects);
+    println!("Checkpoints: {}", info.checkpoints);
+    match info.blocks {
+        Some(blocks) => println!("Blocks:      {} (indexed)", blocks),
+        None => println!("Blocks:      no index, capture wasn't finished"),
+    }
+    if info.truncated {
+        println!("Warning:     last record is truncated");
+    }
+    println!("Messages:");
+    for (kind, count) in &info.messages {
+        println!("  {:<20} {}", kind, count);
+    }
+}
+
+// Rewrite a capture with periodic book checkpoints
+fn write_capture_checkpoints(
+    input: &str,
+    output: &str,
+    every: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut harness = ReplayHarness::open(input)?;
+    let mut recorder = Recorder::create(output, harness.header().clone())?;
+    let written = harness.write_checkpoints(&mut recorder, every)?;
+    println!(
+        "Wrote {} records with {} checkpoints to {}",
+        recorder.records(),
+        written,
+        output
+    );
+    recorder.finish()?;
+    Ok(())
+}
+
 // Run benchmark suite
 #[tokio::main]
 async fn main() {
+    let matches = cli().get_matches();
+    if let Some(("capture", capture)) = matches.subcommand() {
+        match capture.subcommand() {
+            Some(("info", args)) => print_capture_info(args.get_one::<String>("path").unwrap()),
+            Some(("checkpoint", args)) => {
+                let input = args.get_one::<String>("input").unwrap();
+                let output = args.get_one::<String>("output").unwrap();
+                let every = Duration::from_secs(*args.get_one::<u64>("every").unwrap());
+                if let Err(e) = write_capture_checkpoints(input, output, every) {
+                    eprintln!("Failed to checkpoint {}: {}", input, e);
+                    exit(1);
+                }
+            }
+            Some(("book", args)) => {
+                let path = args.get_one::<String>("path").unwrap();
+                let symbol = args.get_one::<String>("symbol").unwrap();
+                let at = args.get_one::<String>("at").unwrap();
+                if let Err(e) = print_capture_book(path, symbol, at) {
+                    eprintln!("Failed to query {}: {}", path, e);
+                    exit(1);
+                }
+            }
+            Some(("diff", args)) => {
+                let path = args.get_one::<String>("path").unwrap();
+                let symbol = args.get_one::<String>("symbol").unwrap();
+                let from = args.get_one::<String>("from").unwrap();
+                let to = args.get_one::<String>("to").unwrap();
+                let json = args.get_flag("json");
+                if let Err(e) = print_capture_diff(path, symbol, from, to, json) {
+                    eprintln!("Failed to query {}: {}", path, e);
+                    exit(1);
+                }
+            }
+            _ => {}
+        }
+        return;
+    }
+
     init_logging();
     preflight_checks();
     collect_system_info();
@@ -321,9 +879,34 @@ async fn main() {
     run_criterion();
     //run_flamegraph();
     run_perf_stat();
-    run_live_latency().await;
+    let resubscribe = !matches
+        .subcommand_matches("benchmark")
+        .is_some_and(|args| args.get_flag("no-resubscribe"));
+    let live = match run_live_latency(resubscribe).await {
+        Ok(report) => Some(report),
+        Err(e) => {
+            error!("Live latency run failed: {}", e);
+            None
+        }
+    };
+
+    let capture = match run_capture_replay() {
+        Ok(report) => report,
+        Err(e) => {
+            error!("Capture replay failed: {}", e);
+            None
+        }
+    };
+
+    let replay = match run_coinbase_replay() {
+        Ok(report) => report,
+        Err(e) => {
+            error!("Coinbase replay failed: {}", e);
+            None
+        }
+    };
 
-    generate_summary();
+    generate_summary(live.as_ref(), capture.as_ref(), replay.as_ref());
 
     info!("{}", "Benchmark Complete");
 }
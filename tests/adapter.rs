@@ -0,0 +1,682 @@
+use matchstick::FeedAdapter;
+use matchstick::adapter::{KrakenAdapter, process_message, process_message_with};
+use matchstick::applier::{
+    Anomaly, ApplyPolicy, ChecksumMismatch, Outcome, ParseError, apply_events, to_order_id,
+};
+use matchstick::audit::AuditLog;
+use matchstick::book::{BookMode, BookSet, BookSide};
+use matchstick::checksum::checksum;
+use matchstick::decimal::DecimalError;
+use matchstick::event::BookEvent;
+use matchstick::instrument::InstrumentSpec;
+use matchstick::messages::{KrakenMessage, Response};
+use matchstick::stats::QualityStats;
+use std::time::Duration;
+
+fn message(kind: &str, bids: &str, asks: &str) -> Response {
+    symbol_message("ETH/BTC", kind, bids, asks)
+}
+
+fn symbol_message(symbol: &str, kind: &str, bids: &str, asks: &str) -> Response {
+    serde_json::from_str(&format!(
+        r#"{{"channel":"level3","type":"{}","data":[{{"symbol":"{}","bids":[{}],"asks":[{}]}}]}}"#,
+        kind, symbol, bids, asks
+    ))
+    .unwrap()
+}
+
+fn order(event: &str, id: &str, price: &str, qty: &str) -> String {
+    order_at(event, id, price, qty, "2026-10-18T12:00:00Z")
+}
+
+fn order_at(event: &str, id: &str, price: &str, qty: &str, timestamp: &str) -> String {
+    format!(
+        r#"{{"event":"{}","order_id":"{}","limit_price":{},"order_qty":{},"timestamp":"{}"}}"#,
+        event, id, price, qty, timestamp
+    )
+}
+
+/// Books for ETH/BTC that have loaded an empty snapshot.
+fn synced_books() -> BookSet {
+    let mut books = BookSet::with_symbols(["ETH/BTC"]);
+    process_message(&mut books, message("snapshot", "", "")).unwrap();
+    books
+}
+
+#[test]
+fn routes_each_symbol_to_its_own_book() {
+    let mut books = BookSet::with_symbols(["ETH/BTC", "BTC/USD"]);
+    let eth = order("add", "OETH1", "0.03010", "1.5");
+    let btc = order("add", "OBTC1", "65000.5", "0.25");
+    process_message(&mut books, message("snapshot", &eth, "")).unwrap();
+    process_message(&mut books, symbol_message("BTC/USD", "snapshot", "", &btc)).unwrap();
+
+    // Each order lands in its own book at that pair's precision
+    let eth_book = books.book("ETH/BTC").unwrap();
+    assert_eq!(eth_book.orders.len(), 1);
+    assert_eq!(eth_book.orders.get("OETH1").unwrap().price, 3010);
+    let btc_book = books.book("BTC/USD").unwrap();
+    assert_eq!(btc_book.orders.len(), 1);
+    let resting = btc_book.orders.get("OBTC1").unwrap();
+    assert_eq!((resting.side, resting.price), (BookSide::Ask, 650005));
+
+    let update = order("delete", "OETH1", "0.03010", "1.5");
+    process_message(&mut books, message("update", &update, "")).unwrap();
+    assert!(books.book("ETH/BTC").unwrap().orders.is_empty());
+    assert_eq!(books.book("BTC/USD").unwrap().orders.len(), 1);
+
+    let other = order("add", "OETH2", "3000.10", "1.0");
+    let result = process_message(
+        &mut books,
+        symbol_message("ETH/USD", "snapshot", &other, ""),
+    );
+    assert!(matches!(result, Err(ParseError::UnknownSymbol(symbol)) if symbol == "ETH/USD"));
+    assert!(books.book("ETH/USD").is_none());
+    assert!(books.book("ETH/BTC").unwrap().orders.is_empty());
+}
+
+#[test]
+fn rejects_unrepresentable_values_without_applying_them() {
+    let mut books = synced_books();
+    let cases = [
+        (order("add", "NEG", "-0.03010", "1.0"), "price"),
+        (order("add", "FINE", "0.030101", "1.0"), "price"),
+        (order("add", "HUGE", "0.03010", "1e20"), "quantity"),
+        (order("add", "DUST", "0.03010", "0.000000001"), "quantity"),
+    ];
+
+    for (event, field) in cases {
+        let valid = order("add", "OK", "0.03010", "1.0");
+        let result = process_message(&mut books, message("update", &valid, &event));
+        match (field, result) {
+            ("price", Err(ParseError::InvalidPrice { .. })) => {}
+            ("quantity", Err(ParseError::InvalidQuantity { .. })) => {}
+            (_, other) => panic!("{} gave {:?}", event, other),
+        }
+    }
+
+    // Nothing from a rejected message reaches the book
+    assert!(books.book("ETH/BTC").unwrap().orders.is_empty());
+}
+
+#[test]
+fn rejects_prices_between_ticks() {
+    let mut books = BookSet::new();
+    books.register_with_spec("ETH/BTC", InstrumentSpec::new(5, 8).with_tick_size(5));
+    process_message(&mut books, message("snapshot", "", "")).unwrap();
+
+    let off_tick = order("add", "OFF", "0.03011", "1.0");
+    let Err(ParseError::InvalidPrice {
+        order_id, reason, ..
+    }) = process_message(&mut books, message("update", &off_tick, ""))
+    else {
+        panic!("expected an off-tick price");
+    };
+    assert_eq!(order_id, "OFF");
+    assert_eq!(reason, DecimalError::OffTick { tick_size: 5 });
+
+    let on_tick = order("add", "ON", "0.03010", "1.0");
+    process_message(&mut books, message("update", &on_tick, "")).unwrap();
+    assert!(books.book("ETH/BTC").unwrap().orders.get("ON").is_some());
+    assert!(books.book("ETH/BTC").unwrap().orders.get("OFF").is_none());
+
+    let spec = InstrumentSpec::for_symbol("BTC/USD").unwrap();
+    assert_eq!((spec.price_precision, spec.tick_size), (1, 1));
+    assert!(spec.is_on_tick(452_853));
+}
+
+#[test]
+fn reports_order_id_and_raw_value() {
+    let mut books = synced_books();
+    let event = order("modify", "OBID1", "0.03010", "-2.5");
+
+    let Err(ParseError::InvalidQuantity {
+        order_id,
+        raw,
+        reason,
+    }) = process_message(&mut books, message("update", &event, ""))
+    else {
+        panic!("expected an invalid quantity");
+    };
+    assert_eq!(order_id, "OBID1");
+    assert_eq!(raw.to_string(), "-2.5");
+    assert_eq!(reason, DecimalError::Negative);
+}
+
+#[test]
+fn ignores_values_on_deletes() {
+    let mut books = synced_books();
+    let add = order("add", "OBID1", "0.03010", "1.5");
+    process_message(&mut books, message("update", &add, "")).unwrap();
+
+    let delete = order("delete", "OBID1", "-1", "-1");
+    process_message(&mut books, message("update", &delete, "")).unwrap();
+    assert!(books.book("ETH/BTC").unwrap().orders.is_empty());
+}
+
+#[test]
+fn snapshot_replaces_the_book() {
+    let mut books = synced_books();
+    let stale = order("add", "STALE", "0.03000", "1.0");
+    process_message(&mut books, message("update", &stale, "")).unwrap();
+
+    let bid = order("add", "OBID1", "0.03010", "1.5");
+    let ask = order("add", "OASK1", "0.03012", "0.8");
+    process_message(&mut books, message("snapshot", &bid, &ask)).unwrap();
+
+    let book = books.book("ETH/BTC").unwrap();
+    assert!(book.is_synced());
+    assert_eq!(book.orders.len(), 2);
+    assert!(book.orders.get("STALE").is_none());
+}
+
+#[test]
+fn flags_updates_before_snapshot() {
+    let mut books = BookSet::with_symbols(["ETH/BTC"]);
+    let add = order("add", "OBID1", "0.03010", "1.5");
+
+    let result = process_message(&mut books, message("update", &add, ""));
+    assert!(matches!(result, Err(ParseError::UpdateBeforeSnapshot(symbol)) if symbol == "ETH/BTC"));
+    assert!(books.book("ETH/BTC").unwrap().orders.is_empty());
+
+    // A resubscribe clears the book, so updates wait for the new snapshot
+    process_message(&mut books, message("snapshot", &add, "")).unwrap();
+    books.reset_symbol("ETH/BTC");
+    let result = process_message(&mut books, message("update", &add, ""));
+    assert!(matches!(result, Err(ParseError::UpdateBeforeSnapshot(_))));
+
+    process_message(&mut books, message("snapshot", &add, "")).unwrap();
+    assert!(books.is_synced("ETH/BTC"));
+    assert_eq!(books.book("ETH/BTC").unwrap().orders.len(), 1);
+}
+
+#[test]
+fn rejects_unknown_message_types() {
+    let mut books = synced_books();
+    let result = process_message(&mut books, message("trade", "", ""));
+    assert!(matches!(result, Err(ParseError::InvalidType)));
+}
+
+#[test]
+fn reports_an_outcome_per_event() {
+    let mut books = synced_books();
+    let bids = [
+        order("add", "OBID1", "0.03010", "1.5"),
+        order("add", "OBID1", "0.03010", "2.0"),
+        order("modify", "OBID1", "0.03010", "1.0"),
+        order("delete", "OBID1", "0.03010", "1.0"),
+    ]
+    .join(",");
+    let ask = order("add", "OASK1", "0.03012", "0.8");
+
+    let mut outcomes = Vec::new();
+    process_message_with(&mut books, message("update", &bids, &ask), |event| {
+        assert_eq!(event.symbol, "ETH/BTC");
+        outcomes.push((event.order_id.to_string(), event.outcome));
+    })
+    .unwrap();
+
+    let expected = [
+        ("OBID1", Outcome::Added),
+        ("OBID1", Outcome::Replaced),
+        ("OBID1", Outcome::Modified),
+        ("OBID1", Outcome::Deleted),
+        ("OASK1", Outcome::Added),
+    ];
+    let expected: Vec<_> = expected
+        .iter()
+        .map(|(id, outcome)| (id.to_string(), *outcome))
+        .collect();
+    assert_eq!(outcomes, expected);
+
+    let missing = order("delete", "GONE", "0.03010", "1.0");
+    let mut rejected = Vec::new();
+    let result = process_message_with(&mut books, message("update", &missing, ""), |event| {
+        rejected.push(event.outcome)
+    });
+    assert!(matches!(result, Err(ParseError::OrderError(_))));
+    assert_eq!(rejected, [Outcome::Rejected]);
+}
+
+fn mixed_batch() -> String {
+    [
+        order("add", "OBID1", "0.03010", "1.5"),
+        order("delete", "GONE", "0.03010", "1.0"),
+        order("add", "BAD", "-0.03010", "1.0"),
+        order("modify", "OBID1", "0.03010", "0"),
+        order("add", "OBID2", "0.03009", "2.0"),
+    ]
+    .join(",")
+}
+
+#[test]
+fn strict_policy_stops_at_first_error() {
+    let mut books = synced_books();
+    let result = process_message(&mut books, message("update", &mixed_batch(), ""));
+    assert!(matches!(result, Err(ParseError::InvalidPrice { .. })));
+    assert!(books.book("ETH/BTC").unwrap().orders.is_empty());
+}
+
+#[test]
+fn lenient_policy_applies_what_it_can() {
+    let mut books = synced_books();
+    books.set_policy(ApplyPolicy::Lenient);
+
+    let report = process_message(&mut books, message("update", &mixed_batch(), "")).unwrap();
+    assert_eq!(report.applied, 3);
+    let skipped: Vec<&str> = report.errors.iter().map(|e| e.order_id.as_str()).collect();
+    assert_eq!(skipped, ["GONE", "BAD"]);
+    let anomalies: Vec<Anomaly> = report.anomalies.iter().map(|a| a.anomaly).collect();
+    assert_eq!(
+        anomalies,
+        [Anomaly::DeleteUnknownOrder, Anomaly::ModifyToZero]
+    );
+
+    let orders = &books.book("ETH/BTC").unwrap().orders;
+    assert!(orders.get("OBID1").is_none());
+    assert!(orders.get("OBID2").is_some());
+    assert!(orders.get("BAD").is_none());
+}
+
+#[test]
+fn audit_policy_writes_anomalies_to_file() {
+    let path = std::env::temp_dir().join(format!("matchstick-audit-{}.jsonl", std::process::id()));
+    let mut books = synced_books();
+    books.audit_to(AuditLog::create(&path).unwrap());
+
+    let duplicate = order("add", "OBID2", "0.03009", "1.0");
+    let batch = format!("{},{}", mixed_batch(), duplicate);
+    process_message(&mut books, message("update", &batch, "")).unwrap();
+    books.audit_log_mut().unwrap().flush().unwrap();
+
+    let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    std::fs::remove_file(&path).unwrap();
+
+    let anomalies: Vec<&str> = lines
+        .iter()
+        .filter_map(|line| line["anomaly"].as_str())
+        .collect();
+    assert_eq!(
+        anomalies,
+        ["delete_unknown_order", "modify_to_zero", "duplicate_add"]
+    );
+    let errors: Vec<&str> = lines
+        .iter()
+        .filter(|line| line.get("error").is_some())
+        .map(|line| line["order_id"].as_str().unwrap())
+        .collect();
+    assert_eq!(errors, ["GONE", "BAD"]);
+}
+
+const REPRICE_FIXTURE: &str = "tests/fixtures/kraken/ethbtc_reprice.jsonl";
+
+fn fixture_messages(path: &str) -> Vec<Response> {
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| match KrakenMessage::parse(line).unwrap() {
+            KrakenMessage::Snapshot(response) | KrakenMessage::Update(response) => response,
+            other => panic!("unexpected fixture message {:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn repriced_modify_loses_queue_priority() {
+    let mut books = BookSet::with_symbols(["ETH/BTC"]);
+    let mut outcomes = Vec::new();
+    for response in fixture_messages(REPRICE_FIXTURE) {
+        process_message_with(&mut books, response, |event| outcomes.push(event.outcome)).unwrap();
+    }
+
+    assert_eq!(
+        outcomes,
+        [
+            Outcome::Added,
+            Outcome::Added,
+            Outcome::Added,
+            Outcome::Repriced,
+            Outcome::Repriced,
+            Outcome::Repriced,
+            Outcome::Modified,
+        ]
+    );
+
+    let orders = &books.book("ETH/BTC").unwrap().orders;
+    let bids: Vec<(u64, Vec<&str>)> = orders
+        .bids()
+        .map(|(price, ids)| (price, ids.iter().map(String::as_str).collect()))
+        .collect();
+    // A moved away and back, so it now queues behind B
+    assert_eq!(
+        bids,
+        [(3010, vec!["OBIDB-AAAAA-000002", "OBIDA-AAAAA-000001"])]
+    );
+    assert_eq!(orders.get("OBIDA-AAAAA-000001").unwrap().qty, 75_000_000);
+    assert_eq!(orders.get("OBIDB-AAAAA-000002").unwrap().qty, 150_000_000);
+
+    let asks: Vec<u64> = orders.asks().map(|(price, _)| price).collect();
+    assert_eq!(asks, [3013]);
+}
+
+#[test]
+fn mirror_mode_parks_crossing_inserts() {
+    let mut books = synced_books();
+    let ask = order("add", "OASK1", "0.03012", "1.0");
+    process_message(&mut books, message("update", "", &ask)).unwrap();
+
+    // Kraken briefly shows a bid through the ask, matchbook must not trade it
+    let bid = order("add", "OBID1", "0.03013", "0.5");
+    let mut outcomes = Vec::new();
+    let report = process_message_with(&mut books, message("update", &bid, ""), |event| {
+        outcomes.push(event.outcome)
+    })
+    .unwrap();
+    assert_eq!(outcomes, [Outcome::Parked]);
+    assert_eq!(report.anomalies.len(), 1);
+    assert_eq!(report.anomalies[0].order_id, "OBID1");
+    assert_eq!(report.anomalies[0].anomaly, Anomaly::CrossingInsert);
+
+    let book = books.book("ETH/BTC").unwrap();
+    assert!(book.is_parked("OBID1"));
+    assert_eq!(book.orders.get("OASK1").unwrap().qty, 100_000_000);
+    assert_eq!(book.orders.get("OBID1").unwrap().qty, 50_000_000);
+
+    // Once the ask goes the bid no longer crosses and rests in matchbook
+    let delete = order("delete", "OASK1", "0.03012", "1.0");
+    process_message(&mut books, message("update", "", &delete)).unwrap();
+    let book = books.book("ETH/BTC").unwrap();
+    assert!(!book.is_parked("OBID1"));
+    assert!(book.parked().is_empty());
+
+    let delete = order("delete", "OBID1", "0.03013", "0.5");
+    process_message(&mut books, message("update", &delete, "")).unwrap();
+    assert!(books.book("ETH/BTC").unwrap().orders.is_empty());
+}
+
+#[test]
+fn releases_parked_orders_as_the_opposite_side_moves() {
+    let mut books = synced_books();
+    let asks = [
+        order("add", "OASK1", "0.03012", "1.0"),
+        order("add", "OASK2", "0.03014", "1.0"),
+    ];
+    process_message(&mut books, message("update", "", &asks.join(","))).unwrap();
+    let bids = [
+        order("add", "OBID1", "0.03013", "0.5"),
+        order("add", "OBID2", "0.03015", "0.5"),
+        order("add", "OBID3", "0.03013", "0.5"),
+    ];
+    process_message(&mut books, message("update", &bids.join(","), "")).unwrap();
+    let parked = |books: &BookSet| -> Vec<String> {
+        let book = books.book("ETH/BTC").unwrap();
+        book.parked().iter().map(str::to_string).collect()
+    };
+    assert_eq!(parked(&books), ["OBID1", "OBID3", "OBID2"]);
+
+    // Same-side changes leave them parked
+    let modify = order("modify", "OBID1", "0.03013", "0.25");
+    process_message(&mut books, message("update", &modify, "")).unwrap();
+    assert_eq!(parked(&books), ["OBID1", "OBID3", "OBID2"]);
+
+    // The best ask moves to 0.03014, freeing only the bids below it
+    let delete = order("delete", "OASK1", "0.03012", "1.0");
+    process_message(&mut books, message("update", "", &delete)).unwrap();
+    assert_eq!(parked(&books), ["OBID2"]);
+
+    let delete = order("delete", "OASK2", "0.03014", "1.0");
+    process_message(&mut books, message("update", "", &delete)).unwrap();
+    assert!(parked(&books).is_empty());
+    assert_eq!(books.book("ETH/BTC").unwrap().orders.len(), 3);
+}
+
+#[test]
+fn matching_mode_times_matching_after_inserts() {
+    let mut books = synced_books();
+    books.set_mode(BookMode::Matching);
+    let bid = order("add", "OBID1", "0.03013", "0.5");
+    let ask = order("add", "OASK1", "0.03012", "1.0");
+
+    let mut outcomes = Vec::new();
+    let report = process_message_with(&mut books, message("update", &bid, &ask), |event| {
+        outcomes.push((event.outcome, event.matching.is_some()))
+    })
+    .unwrap();
+    assert_eq!(outcomes, [(Outcome::Added, true), (Outcome::Added, true)]);
+    assert!(report.anomalies.is_empty());
+    assert!(books.book("ETH/BTC").unwrap().parked().is_empty());
+}
+
+#[test]
+fn matching_mode_follows_the_venue_after_fills() {
+    let mut books = synced_books();
+    books.set_mode(BookMode::Matching);
+    assert_eq!(books.policy(), ApplyPolicy::Strict);
+    let bid = order("add", "OBID1", "0.03013", "1.0");
+    let ask = order("add", "OASK1", "0.03012", "1.0");
+    process_message(&mut books, message("update", &bid, &ask)).unwrap();
+
+    // Both crossed, so matchbook fills them whatever the venue shows
+    let book = books.book_mut("ETH/BTC").unwrap();
+    for id in ["OBID1", "OASK1"] {
+        let _ = book.orderbook.cancel_order(to_order_id(id));
+    }
+
+    // Kraken then replaces one and deletes the other, with a checksum over
+    // its own book
+    let events: Response = serde_json::from_str(&format!(
+        r#"{{"channel":"level3","type":"update","data":[{{"symbol":"ETH/BTC","checksum":1,"bids":[{}],"asks":[{}]}}]}}"#,
+        order("add", "OBID1", "0.03011", "2.0"),
+        order("delete", "OASK1", "0.03012", "1.0"),
+    ))
+    .unwrap();
+    let report = process_message(&mut books, events).unwrap();
+    assert!(report.errors.is_empty());
+    let consumed: Vec<&str> = report
+        .anomalies
+        .iter()
+        .filter(|record| record.anomaly == Anomaly::MatchConsumed)
+        .map(|record| record.order_id.as_str())
+        .collect();
+    assert_eq!(consumed, ["OBID1", "OASK1"]);
+
+    // The replacement still went in
+    let book = books.book_mut("ETH/BTC").unwrap();
+    assert_eq!(book.orders.len(), 1);
+    assert_eq!(book.orders.get("OBID1").unwrap().price, 3011);
+    assert!(book.orderbook.cancel_order(to_order_id("OBID1")).is_ok());
+}
+
+#[test]
+fn normalizes_kraken_messages_to_book_events() {
+    let text = r#"{"channel":"level3","type":"snapshot","data":[{"symbol":"ETH/BTC","checksum":42,"bids":[{"order_id":"OBID1","limit_price":0.03010,"order_qty":1.5,"timestamp":"2026-10-18T12:00:00Z"}],"asks":[]}]}"#;
+    let mut adapter = KrakenAdapter::default();
+    let mut events = Vec::new();
+    adapter
+        .normalize(&KrakenMessage::parse(text).unwrap(), &mut events)
+        .unwrap();
+    adapter
+        .normalize(&KrakenMessage::Heartbeat, &mut events)
+        .unwrap();
+
+    let symbol = || "ETH/BTC".to_string();
+    assert!(matches!(&events[0], BookEvent::SnapshotStart { symbol } if symbol == "ETH/BTC"));
+    let BookEvent::AddOrder { order, .. } = &events[1] else {
+        panic!("expected an add, got {:?}", events[1]);
+    };
+    assert_eq!(order.order_id, "OBID1");
+    assert_eq!(order.side, BookSide::Bid);
+    assert_eq!(order.price.to_string(), "0.0301");
+    assert_eq!(
+        events[2..],
+        [
+            BookEvent::SnapshotEnd { symbol: symbol() },
+            BookEvent::Checksum {
+                symbol: symbol(),
+                expected: 42,
+            },
+            BookEvent::Heartbeat,
+        ]
+    );
+}
+
+#[test]
+fn tracks_update_timestamps_per_symbol() {
+    let mut adapter = KrakenAdapter::with_gap_threshold(Duration::from_secs(1));
+    let mut books = synced_books();
+    let updates = [
+        ("OBID1", "2026-10-18T12:00:01Z"),
+        ("OBID2", "2026-10-18T12:00:00.500Z"),
+        ("OBID3", "2026-10-18T12:00:01.800Z"),
+        ("OBID4", "2026-10-18T12:00:04Z"),
+    ];
+
+    let mut gaps = Vec::new();
+    for (id, timestamp) in updates {
+        let bid = order_at("add", id, "0.03010", "1.0", timestamp);
+        let received = timestamp.parse::<chrono::DateTime<chrono::Utc>>().unwrap()
+            + chrono::TimeDelta::milliseconds(250);
+        let mut events = Vec::new();
+        adapter
+            .normalize_response_at(&message("update", &bid, ""), received, &mut events)
+            .unwrap();
+        apply_events(&adapter, &mut books, &events, |_| {}).unwrap();
+        gaps.extend(events.into_iter().filter_map(|event| match event {
+            BookEvent::Gap { since, at, .. } => Some((since.to_rfc3339(), at.to_rfc3339())),
+            _ => None,
+        }));
+    }
+
+    // The late update is still applied, but doesn't move the clock back
+    assert_eq!(books.book("ETH/BTC").unwrap().orders.len(), 4);
+    assert_eq!(
+        gaps,
+        [(
+            "2026-10-18T12:00:01.800+00:00".to_string(),
+            "2026-10-18T12:00:04+00:00".to_string()
+        )]
+    );
+
+    let stats = adapter.clock().stats();
+    assert_eq!(stats.out_of_order, 1);
+    assert_eq!(stats.gaps, 1);
+    assert_eq!(stats.largest_gap, Duration::from_millis(2200));
+    let latency = Duration::from_nanos(stats.latency().p99);
+    assert!(latency.abs_diff(Duration::from_millis(250)) < Duration::from_millis(1));
+
+    // A snapshot restarts the clock, so resubscribing isn't a gap
+    let snapshot = message("snapshot", "", "");
+    let mut events = Vec::new();
+    adapter.normalize_response(&snapshot, &mut events).unwrap();
+    assert!(adapter.clock().latest("ETH/BTC").is_none());
+}
+
+/// Eleven levels a side with the best ask two orders deep, as a snapshot
+/// carrying `checksum`.
+/// A snapshot data block for ETH/BTC eleven levels deep on each side.
+fn deep_block(checksum: u32) -> String {
+    let mut asks = Vec::new();
+    let mut bids = Vec::new();
+    for level in 0..11 {
+        let qty = if level == 0 {
+            "0.00500000".to_string()
+        } else {
+            format!("{}.25000000", level + 1)
+        };
+        let ask = format!("0.{:05}", 3012 + level);
+        asks.push(order("add", &format!("OASK{}", level), &ask, &qty));
+        if level == 0 {
+            asks.push(order("add", "OASK0B", &ask, "12.00000000"));
+        }
+        let bid = format!("0.{:05}", 3010 - level);
+        let qty = format!("0.{:02}000000", level + 1);
+        bids.push(order("add", &format!("OBID{}", level), &bid, &qty));
+    }
+    format!(
+        r#"{{"symbol":"ETH/BTC","checksum":{},"bids":[{}],"asks":[{}]}}"#,
+        checksum,
+        bids.join(","),
+        asks.join(",")
+    )
+}
+
+fn deep_snapshot(checksum: u32) -> Response {
+    serde_json::from_str(&format!(
+        r#"{{"channel":"level3","type":"snapshot","data":[{}]}}"#,
+        deep_block(checksum)
+    ))
+    .unwrap()
+}
+
+/// CRC32 of the best ten ask levels then the best ten bid levels, each
+/// order as its price and quantity with the point and leading zeros removed.
+const DEEP_SNAPSHOT_CHECKSUM: u32 = 250363933;
+
+#[test]
+fn verifies_checksums_over_the_top_ten_levels() {
+    let top_ten = concat!(
+        "3012500000",
+        "30121200000000",
+        "3013225000000",
+        "3014325000000",
+        "3015425000000",
+        "3016525000000",
+        "3017625000000",
+        "3018725000000",
+        "3019825000000",
+        "3020925000000",
+        "30211025000000",
+        "30101000000",
+        "30092000000",
+        "30083000000",
+        "30074000000",
+        "30065000000",
+        "30056000000",
+        "30047000000",
+        "30038000000",
+        "30029000000",
+        "300110000000",
+    );
+    assert_eq!(crc32fast::hash(top_ten.as_bytes()), DEEP_SNAPSHOT_CHECKSUM);
+
+    let mut books = BookSet::with_symbols(["ETH/BTC"]);
+    process_message(&mut books, deep_snapshot(DEEP_SNAPSHOT_CHECKSUM)).unwrap();
+    let book = books.book("ETH/BTC").unwrap();
+    assert_eq!(book.orders.len(), 23);
+    assert_eq!(checksum(&book.orders, &book.spec), DEEP_SNAPSHOT_CHECKSUM);
+}
+
+#[test]
+fn reports_books_that_fail_their_checksum() {
+    let mut books = BookSet::with_symbols(["ETH/BTC", "BTC/USD"]);
+    let snapshots: Response = serde_json::from_str(&format!(
+        r#"{{"channel":"level3","type":"snapshot","data":[{},{{"symbol":"BTC/USD","bids":[{}],"asks":[]}}]}}"#,
+        deep_block(DEEP_SNAPSHOT_CHECKSUM + 1),
+        order("add", "OBTC1", "65000.5", "0.25"),
+    ))
+    .unwrap();
+    let report = process_message(&mut books, snapshots).unwrap();
+    assert_eq!(
+        report.mismatches,
+        [ChecksumMismatch {
+            symbol: "ETH/BTC".to_string(),
+            expected: DEEP_SNAPSHOT_CHECKSUM + 1,
+            actual: DEEP_SNAPSHOT_CHECKSUM,
+        }]
+    );
+
+    // The block after the mismatch still applied
+    let btc = books.book("BTC/USD").unwrap();
+    assert!(btc.is_synced());
+    assert!(btc.orders.get("OBTC1").is_some());
+
+    let mut quality = QualityStats::default();
+    quality.record_report(&report);
+    assert_eq!(quality.checksum_mismatches, 1);
+    assert!(!quality.record_error(&ParseError::UnknownSymbol("XBT/EUR".to_string())));
+    assert_eq!(quality.checksum_mismatches, 1);
+}
@@ -0,0 +1,81 @@
+use matchstick::FeedAdapter;
+use matchstick::applier::{Anomaly, Outcome};
+use matchstick::book::{BookSet, BookSide};
+use matchstick::coinbase::adapter::{CoinbaseAdapter, replay_lines};
+use matchstick::coinbase::messages::CoinbaseMessage;
+use matchstick::stats::SymbolLatencyStats;
+use std::fs::File;
+use std::io::BufReader;
+
+const FIXTURE: &str = "tests/fixtures/coinbase/btcusd_full.jsonl";
+
+#[test]
+fn replays_full_channel_capture() {
+    let mut books = BookSet::new();
+    let mut stats = SymbolLatencyStats::new();
+    let mut outcomes = Vec::new();
+    let reader = BufReader::new(File::open(FIXTURE).unwrap());
+
+    let summary = replay_lines(reader, &mut books, |event| {
+        stats.record(&event);
+        outcomes.push((event.order_id.to_string(), event.outcome));
+    })
+    .unwrap();
+
+    assert_eq!(summary.lines, 13);
+    assert_eq!(summary.malformed, 1);
+    assert_eq!(summary.failed, 0);
+    assert_eq!(summary.applied, 7);
+    assert_eq!(summary.anomalies, 0);
+
+    let expected = [
+        ("b-snap-1", Outcome::Added),
+        ("a-snap-1", Outcome::Added),
+        ("o-1", Outcome::Added),
+        ("o-1", Outcome::Modified),
+        ("o-1", Outcome::Modified),
+        ("a-snap-1", Outcome::Deleted),
+        ("b-snap-1", Outcome::Deleted),
+    ];
+    let expected: Vec<_> = expected
+        .iter()
+        .map(|(id, outcome)| (id.to_string(), *outcome))
+        .collect();
+    assert_eq!(outcomes, expected);
+
+    let book = books.book("BTC-USD").unwrap();
+    assert_eq!(book.orders.len(), 1);
+    let resting = book.orders.get("o-1").unwrap();
+    assert_eq!(resting.side, BookSide::Ask);
+    assert_eq!(book.spec.format_price(resting.price), "100.50000000");
+    assert_eq!(book.spec.format_qty(resting.qty), "0.80000000");
+
+    assert!(stats.get("BTC-USD").is_some());
+}
+
+#[test]
+fn reports_fills_larger_than_the_maker_order() {
+    let lines = [
+        r#"{"type":"open","product_id":"BTC-USD","sequence":1,"order_id":"o-1","side":"sell","price":"100.50","remaining_size":"1.00000000","time":"2026-10-18T12:00:00.100000Z"}"#,
+        r#"{"type":"match","product_id":"BTC-USD","sequence":2,"trade_id":1,"maker_order_id":"o-1","taker_order_id":"o-2","side":"sell","price":"100.50","size":"1.50000000","time":"2026-10-18T12:00:00.200000Z"}"#,
+    ];
+    let mut books = BookSet::new();
+    let summary = replay_lines(lines.join("\n").as_bytes(), &mut books, |_| {}).unwrap();
+
+    assert_eq!(summary.anomalies, 1);
+    assert_eq!(summary.failed, 0);
+    // Still removed, as nothing can be left of it
+    assert!(books.book("BTC-USD").unwrap().orders.is_empty());
+
+    let mut adapter = CoinbaseAdapter::new();
+    let mut events = Vec::new();
+    for line in lines {
+        let message = CoinbaseMessage::parse(line).unwrap();
+        adapter.normalize(&message, &mut events).unwrap();
+    }
+    let anomalies = adapter.take_anomalies();
+    assert_eq!(anomalies.len(), 1);
+    assert_eq!(anomalies[0].anomaly, Anomaly::Overfill);
+    assert_eq!(anomalies[0].order_id, "o-1");
+    assert!(adapter.take_anomalies().is_empty());
+}
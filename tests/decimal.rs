@@ -0,0 +1,138 @@
+use matchstick::decimal::{Decimal, DecimalError};
+use matchstick::messages::OrderEvent;
+use proptest::prelude::*;
+
+/// Scales a plain `int.frac` string by moving the decimal point on the text
+/// itself, as an independent reference for `Decimal::rescale`.
+fn reference_rescale(int: &str, frac: &str, precision: u32) -> Result<u64, DecimalError> {
+    let precision = precision as usize;
+    let (kept, dropped) = frac.split_at(frac.len().min(precision));
+    if dropped.bytes().any(|b| b != b'0') {
+        return Err(DecimalError::TooPrecise {
+            precision: precision as u32,
+        });
+    }
+    let digits = format!("{}{:0<width$}", int, kept, width = precision);
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    digits.parse().map_err(|_| DecimalError::Overflow)
+}
+
+fn number_text(int: u64, frac: &str) -> String {
+    if frac.is_empty() {
+        int.to_string()
+    } else {
+        format!("{}.{}", int, frac)
+    }
+}
+
+proptest! {
+    #[test]
+    fn rescale_matches_reference(
+        int in any::<u64>(),
+        frac in "[0-9]{0,12}",
+        precision in 0u32..=18,
+    ) {
+        let text = number_text(int, &frac);
+        let decimal: Decimal = text.parse().unwrap();
+        prop_assert_eq!(
+            decimal.rescale(precision),
+            reference_rescale(&int.to_string(), &frac, precision)
+        );
+    }
+
+    #[test]
+    fn exponent_form_rescales_like_plain_form(
+        mantissa in 0u64..1_000_000_000,
+        exponent in -12i32..=6,
+        precision in 0u32..=18,
+    ) {
+        let exponential: Decimal = format!("{}e{}", mantissa, exponent).parse().unwrap();
+        let plain = if exponent >= 0 {
+            format!("{}{}", mantissa, "0".repeat(exponent as usize))
+        } else {
+            let digits = format!("{:0>width$}", mantissa, width = -exponent as usize + 1);
+            let (int, frac) = digits.split_at(digits.len() - (-exponent) as usize);
+            format!("{}.{}", int, frac)
+        };
+        let plain: Decimal = plain.parse().unwrap();
+        prop_assert_eq!(exponential, plain);
+        prop_assert_eq!(exponential.rescale(precision), plain.rescale(precision));
+    }
+
+    #[test]
+    fn display_round_trips(int in any::<u64>(), frac in "[0-9]{0,12}") {
+        let decimal: Decimal = number_text(int, &frac).parse().unwrap();
+        let reparsed: Decimal = decimal.to_string().parse().unwrap();
+        prop_assert_eq!(decimal, reparsed);
+    }
+
+    #[test]
+    fn deserializes_json_numbers_exactly(int in 0u64..1_000_000, frac in "[0-9]{1,8}") {
+        let text = number_text(int, &frac);
+        let json = format!(
+            r#"{{"order_id":"O1","limit_price":{},"order_qty":{},"timestamp":"2026-10-18T12:00:00Z"}}"#,
+            text, text
+        );
+        let event: OrderEvent = serde_json::from_str(&json).unwrap();
+        let expected = reference_rescale(&int.to_string(), &frac, 8).unwrap();
+        prop_assert_eq!(event.limit_price.rescale(8), Ok(expected));
+        prop_assert_eq!(event.order_qty.rescale(8), Ok(expected));
+    }
+}
+
+#[test]
+fn exact_text_parse_differs_from_f64_truncation() {
+    let decimal: Decimal = "0.29".parse().unwrap();
+    assert_eq!((0.29f64 * 100.0) as u64, 28);
+    assert_eq!(decimal.rescale(2), Ok(29));
+    assert_eq!(decimal.rescale(5), Ok(29_000));
+}
+
+#[test]
+fn rejects_inexact_or_invalid_values() {
+    let price: Decimal = "0.030115".parse().unwrap();
+    assert_eq!(
+        price.rescale(5),
+        Err(DecimalError::TooPrecise { precision: 5 })
+    );
+
+    let negative: Decimal = "-1.5".parse().unwrap();
+    assert_eq!(negative.rescale(8), Err(DecimalError::Negative));
+
+    let huge: Decimal = "1e30".parse().unwrap();
+    assert_eq!(huge.rescale(0), Err(DecimalError::Overflow));
+
+    for text in [
+        "1.5e-9223372036854775807",
+        "1e-9223372036854775808",
+        "0.0e-9223372036854775808",
+        "1e9223372036854775807",
+        "-1.5e9223372036854775807",
+        "1e-4294967296",
+    ] {
+        assert_eq!(
+            text.parse::<Decimal>(),
+            Err(DecimalError::Overflow),
+            "{}",
+            text
+        );
+    }
+    let tiny: Decimal = "1e-4294967295".parse().unwrap();
+    assert_eq!(
+        tiny.rescale(8),
+        Err(DecimalError::TooPrecise { precision: 8 })
+    );
+    let zero: Decimal = "0e9223372036854775807".parse().unwrap();
+    assert_eq!(zero.rescale(8), Ok(0));
+
+    for text in ["", "-", "1.", ".5", "1e", "NaN", "0x10", "1.2.3"] {
+        assert!(
+            matches!(text.parse::<Decimal>(), Err(DecimalError::Syntax(_))),
+            "{:?} should not parse",
+            text
+        );
+    }
+}
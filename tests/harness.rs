@@ -0,0 +1,374 @@
+use chrono::DateTime;
+use matchstick::checkpoint::CheckpointOrder;
+use matchstick::harness::{InvalidSpeed, ReplayHarness, ReplaySpeed};
+use matchstick::instrument::InstrumentSpec;
+use matchstick::query::{CaptureQuery, QueryError};
+use matchstick::recorder::{
+    CaptureHeader, CaptureReader, InstrumentHeader, Record, RecordKind, Recorder,
+    SubscriptionRecord,
+};
+use matchstick::subscription::SubscriptionConfig;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const FIXTURE: &str = "tests/fixtures/kraken/ethbtc_l3.jsonl";
+
+fn capture_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "matchstick-harness-{}-{}.capture",
+        name,
+        std::process::id()
+    ))
+}
+
+/// Records the fixture's frames as if they had arrived on one connection.
+fn record_fixture(path: &Path, spacing: Duration) {
+    let header = CaptureHeader::kraken(&SubscriptionConfig::default());
+    let mut recorder = Recorder::create(path, header).unwrap();
+    for line in std::fs::read_to_string(FIXTURE).unwrap().lines() {
+        recorder.record_frame(1, line).unwrap();
+        // A block per frame, so a time range can be sought to
+        recorder.flush().unwrap();
+        std::thread::sleep(spacing);
+    }
+    recorder.record_frame(1, "{not json").unwrap();
+    recorder.flush().unwrap();
+}
+
+#[test]
+fn replays_to_identical_books() {
+    let path = capture_path("deterministic");
+    record_fixture(&path, Duration::ZERO);
+
+    let mut states = Vec::new();
+    for _ in 0..2 {
+        let mut harness = ReplayHarness::open(&path).unwrap();
+        let report = harness.run().unwrap();
+        assert_eq!(report.frames, 5);
+        assert_eq!(report.malformed, 1);
+        assert_eq!(report.failed, 0);
+        assert_eq!(report.quality.anomalies, 0);
+
+        let latency = report.latency.get("ETH/BTC").unwrap().get_stats();
+        assert!(latency.add_order.p99 > 0);
+        let book = harness.books().book("ETH/BTC").unwrap();
+        assert!(book.is_synced());
+        states.push(book.orders.clone());
+    }
+
+    assert_eq!(states[0], states[1]);
+    let asks: Vec<u64> = states[0].asks().map(|(price, _)| price).collect();
+    assert_eq!(asks, [3012, 3013]);
+    assert_eq!(
+        states[0].get("OASK2-AAAAA-000004").unwrap().qty,
+        300_000_000
+    );
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn paces_replay_to_recorded_time() {
+    let path = capture_path("paced");
+    record_fixture(&path, Duration::from_millis(20));
+
+    // Four 20ms gaps between the five frames
+    let real_time = ReplayHarness::open(&path)
+        .unwrap()
+        .speed(ReplaySpeed::RealTime)
+        .unwrap()
+        .run()
+        .unwrap();
+    assert!(real_time.elapsed >= Duration::from_millis(80));
+
+    let scaled = ReplayHarness::open(&path)
+        .unwrap()
+        .speed(ReplaySpeed::Scaled(4.0))
+        .unwrap()
+        .run()
+        .unwrap();
+    assert!(scaled.elapsed >= Duration::from_millis(20));
+    assert!(scaled.elapsed < real_time.elapsed);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn rejects_speed_factors_that_cannot_pace() {
+    let path = capture_path("bad-speed");
+    record_fixture(&path, Duration::ZERO);
+
+    for factor in [0.0, -2.0, f64::NAN, f64::INFINITY] {
+        let result = ReplayHarness::open(&path)
+            .unwrap()
+            .speed(ReplaySpeed::Scaled(factor));
+        assert!(
+            matches!(result, Err(InvalidSpeed(rejected)) if rejected.to_bits() == factor.to_bits())
+        );
+    }
+    assert!(ReplaySpeed::Scaled(0.5).validate().is_ok());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn replays_a_time_range() {
+    let path = capture_path("range");
+    record_fixture(&path, Duration::from_millis(5));
+    let index = CaptureReader::open(&path)
+        .unwrap()
+        .index()
+        .unwrap()
+        .to_vec();
+
+    // Just the two updates after the snapshot
+    let from = DateTime::from_timestamp_nanos(index[1].first_wall_ns);
+    let to = DateTime::from_timestamp_nanos(index[2].last_wall_ns);
+    let mut harness = ReplayHarness::open(&path)
+        .unwrap()
+        .between(from, to)
+        .unwrap();
+    let report = harness.run().unwrap();
+
+    // Without a checkpoint the snapshot before the range is replayed unseen
+    assert_eq!(report.frames, 2);
+    assert_eq!(report.quality.unsynced_updates, 0);
+    let book = harness.books().book("ETH/BTC").unwrap();
+    assert!(book.is_synced());
+    assert!(!book.orders.is_empty());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn paces_a_time_range_from_its_first_record() {
+    let path = capture_path("paced-range");
+    record_fixture(&path, Duration::from_millis(30));
+    let index = CaptureReader::open(&path)
+        .unwrap()
+        .index()
+        .unwrap()
+        .to_vec();
+
+    // The last three frames, two 30ms gaps apart, after 60ms of catch-up
+    let from = DateTime::from_timestamp_nanos(index[2].first_wall_ns);
+    let to = DateTime::from_timestamp_nanos(index[4].last_wall_ns);
+    let report = ReplayHarness::open(&path)
+        .unwrap()
+        .speed(ReplaySpeed::RealTime)
+        .unwrap()
+        .between(from, to)
+        .unwrap()
+        .run()
+        .unwrap();
+    assert_eq!(report.frames, 3);
+    assert!(report.elapsed >= Duration::from_millis(60));
+    assert!(report.elapsed < Duration::from_millis(120));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn restores_symbols_subscribed_after_the_header() {
+    let path = capture_path("subscribed-range");
+    let checkpointed = capture_path("subscribed-checkpointed");
+    let mut recorder =
+        Recorder::create(&path, CaptureHeader::kraken(&SubscriptionConfig::default())).unwrap();
+    let subscribed = SubscriptionConfig {
+        symbols: vec!["ETH/BTC".to_string(), "ETH/USD".to_string()],
+        ..SubscriptionConfig::default()
+    };
+    recorder.record_subscription(1, &subscribed).unwrap();
+    let snapshot = std::fs::read_to_string(FIXTURE).unwrap();
+    let frames = [
+        r#"{"channel":"level3","type":"snapshot","data":[{"symbol":"ETH/USD","bids":[{"order_id":"OBID1","limit_price":3000.10,"order_qty":1.0,"timestamp":"2026-10-18T12:00:00.000000Z"}],"asks":[]}]}"#,
+        snapshot.lines().next().unwrap(),
+        r#"{"channel":"level3","type":"update","data":[{"symbol":"ETH/USD","bids":[{"event":"add","order_id":"OBID2","limit_price":3000.05,"order_qty":2.0,"timestamp":"2026-10-18T12:00:01.000000Z"}],"asks":[]}]}"#,
+    ];
+    for frame in frames {
+        recorder.record_frame(1, frame).unwrap();
+        recorder.flush().unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    recorder.finish().unwrap();
+
+    // Checkpoints once both books have a snapshot
+    let mut harness = ReplayHarness::open(&path).unwrap();
+    let mut recorder = Recorder::create(&checkpointed, harness.header().clone()).unwrap();
+    let written = harness
+        .write_checkpoints(&mut recorder, Duration::ZERO)
+        .unwrap();
+    recorder.finish().unwrap();
+    assert_eq!(written, 2);
+
+    // The range seeks past the subscription record, straight to the first
+    let index = CaptureReader::open(&checkpointed)
+        .unwrap()
+        .index()
+        .unwrap()
+        .to_vec();
+    let first = index.iter().find(|block| block.checkpoint).unwrap();
+    let from = DateTime::from_timestamp_nanos(first.first_wall_ns);
+    let to = DateTime::from_timestamp_nanos(index.last().unwrap().last_wall_ns);
+    let mut ranged = ReplayHarness::open(&checkpointed)
+        .unwrap()
+        .between(from, to)
+        .unwrap();
+    let report = ranged.run().unwrap();
+    assert_eq!(report.failed, 0);
+    assert_eq!(report.checkpoint_mismatches, 0);
+    assert!(ranged.books().book("ETH/BTC").unwrap().is_synced());
+    let book = ranged.books().book("ETH/USD").unwrap();
+    assert_eq!(book.spec, InstrumentSpec::for_symbol("ETH/USD").unwrap());
+    assert_eq!(book.orders.get("OBID2").unwrap().price, 300005);
+
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(checkpointed).unwrap();
+}
+
+#[test]
+fn registers_books_for_later_subscriptions() {
+    let path = capture_path("subscribed");
+    let header = CaptureHeader::kraken(&SubscriptionConfig::default());
+    let mut recorder = Recorder::create(&path, header).unwrap();
+    let subscribed = SubscriptionRecord {
+        subscription: SubscriptionConfig {
+            symbols: vec!["ETH/BTC".to_string(), "XYZ/USD".to_string()],
+            ..SubscriptionConfig::default()
+        },
+        instruments: vec![InstrumentHeader::new("XYZ/USD", InstrumentSpec::new(3, 4))],
+    };
+    recorder
+        .write(&Record {
+            kind: RecordKind::Subscription,
+            mono_ns: 0,
+            wall_ns: 0,
+            connection: 1,
+            payload: serde_json::to_vec(&subscribed).unwrap(),
+        })
+        .unwrap();
+    recorder
+        .record_frame(
+            1,
+            r#"{"channel":"level3","type":"snapshot","data":[{"symbol":"XYZ/USD","bids":[{"order_id":"OBID1","limit_price":1.234,"order_qty":2.5,"timestamp":"2026-10-18T12:00:00.000000Z"}],"asks":[]}]}"#,
+        )
+        .unwrap();
+    recorder.finish().unwrap();
+
+    let mut harness = ReplayHarness::open(&path).unwrap();
+    let report = harness.run().unwrap();
+    assert_eq!((report.failed, report.malformed), (0, 0));
+    let book = harness.books().book("XYZ/USD").unwrap();
+    assert!(book.is_synced());
+    let order = book.orders.get("OBID1").unwrap();
+    assert_eq!((order.price, order.qty), (1234, 25_000));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn starts_a_range_from_the_nearest_checkpoint() {
+    let path = capture_path("uncheckpointed");
+    let checkpointed = capture_path("checkpointed");
+    record_fixture(&path, Duration::from_millis(5));
+
+    let mut harness = ReplayHarness::open(&path).unwrap();
+    let mut recorder = Recorder::create(&checkpointed, harness.header().clone()).unwrap();
+    let written = harness
+        .write_checkpoints(&mut recorder, Duration::ZERO)
+        .unwrap();
+    recorder.finish().unwrap();
+    // One after the snapshot and every frame following it
+    assert_eq!(written, 5);
+
+    let mut full = ReplayHarness::open(&checkpointed).unwrap();
+    let report = full.run().unwrap();
+    assert_eq!(report.checkpoints, 5);
+    assert_eq!(report.checkpoint_mismatches, 0);
+    let expected = full.books().book("ETH/BTC").unwrap().orders.clone();
+
+    let reader = CaptureReader::open(&checkpointed).unwrap();
+    let index = reader.index().unwrap().to_vec();
+    let checkpoints: Vec<_> = index.iter().filter(|block| block.checkpoint).collect();
+    // The one taken before the trailing malformed frame
+    let last = checkpoints[checkpoints.len() - 2];
+    let mut records = CaptureReader::open(&checkpointed).unwrap();
+    records
+        .seek_to_checkpoint(DateTime::from_timestamp_nanos(last.first_wall_ns))
+        .unwrap();
+    assert_eq!(
+        records.next_record().unwrap().unwrap().kind,
+        RecordKind::Checkpoint
+    );
+
+    let from = DateTime::from_timestamp_nanos(last.first_wall_ns);
+    let to = DateTime::from_timestamp_nanos(index.last().unwrap().last_wall_ns);
+    let mut ranged = ReplayHarness::open(&checkpointed)
+        .unwrap()
+        .between(from, to)
+        .unwrap();
+    let report = ranged.run().unwrap();
+    // Loaded from one, checked against the next
+    assert_eq!(report.checkpoints, 2);
+    assert_eq!(report.checkpoint_mismatches, 0);
+    assert_eq!(report.frames, 1);
+    let book = ranged.books().book("ETH/BTC").unwrap();
+    assert!(book.is_synced());
+    assert_eq!(book.orders, expected);
+
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(checkpointed).unwrap();
+}
+
+#[test]
+fn queries_books_and_diffs_at_points_in_time() {
+    let path = capture_path("query");
+    record_fixture(&path, Duration::from_millis(5));
+    let index = CaptureReader::open(&path)
+        .unwrap()
+        .index()
+        .unwrap()
+        .to_vec();
+    let after_frame = |frame: usize| DateTime::from_timestamp_nanos(index[frame].last_wall_ns);
+
+    let query = CaptureQuery::open(&path).unwrap();
+    let before = DateTime::from_timestamp_nanos(index[0].first_wall_ns - 1);
+    assert!(!query.book_at("ETH/BTC", before).unwrap().is_synced());
+
+    let book = query.book_at("ETH/BTC", after_frame(0)).unwrap();
+    assert!(book.is_synced());
+    assert!(book.orders.get("OBID2-AAAAA-000002").is_some());
+    assert!(book.orders.get("OBID3-AAAAA-000005").is_none());
+
+    let diff = query
+        .diff("ETH/BTC", after_frame(0), after_frame(3))
+        .unwrap();
+    let ids = |orders: &[CheckpointOrder]| -> Vec<String> {
+        orders.iter().map(|order| order.order_id.clone()).collect()
+    };
+    assert_eq!(ids(&diff.added), ["OBID3-AAAAA-000005"]);
+    assert_eq!(ids(&diff.removed), ["OBID2-AAAAA-000002"]);
+    assert_eq!(diff.resized.len(), 1);
+    assert_eq!(diff.resized[0].order_id, "OASK2-AAAAA-000004");
+    assert_eq!(
+        (diff.resized[0].before, diff.resized[0].after),
+        (325_000_000, 300_000_000)
+    );
+    assert!(
+        query
+            .diff("ETH/BTC", after_frame(3), after_frame(3))
+            .unwrap()
+            .is_empty()
+    );
+
+    assert!(matches!(
+        query.book_at("XBT/EUR", after_frame(0)),
+        Err(QueryError::UnknownSymbol(_))
+    ));
+    assert!(matches!(
+        query.diff("ETH/BTC", after_frame(3), after_frame(0)),
+        Err(QueryError::InvalidRange { .. })
+    ));
+
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,473 @@
+use futures_util::StreamExt;
+use matchstick::adapter::process_message;
+use matchstick::book::{BookSet, BookSide};
+use matchstick::client::{
+    ConnectionError, FeedEvent, KrakenClient, KrakenClientBuilder, ReconnectPolicy, RejectReason,
+};
+use matchstick::messages::{KrakenMessage, Response};
+use matchstick::mock::{MOCK_API_KEY, MockConfig, MockKraken, Script, Step};
+use matchstick::recorder::{CaptureReader, RecordKind};
+use matchstick::stats::QualityStats;
+use matchstick::token::{Credentials, TokenManager};
+use std::time::Duration;
+
+const FIXTURE: &str = "tests/fixtures/kraken/ethbtc_l3.jsonl";
+
+fn builder(mock: &MockKraken) -> KrakenClientBuilder {
+    KrakenClient::builder()
+        .endpoints(mock.endpoints())
+        .credentials(MockKraken::credentials())
+        .reconnect(ReconnectPolicy {
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(50),
+            max_attempts: Some(3),
+        })
+        .ack_timeout(Duration::from_secs(2))
+}
+
+async fn connect(mock: &MockKraken) -> KrakenClient {
+    builder(mock).connect().await.expect("connect to mock")
+}
+
+/// Reads messages until an L3 snapshot or update arrives.
+async fn next_response(client: &mut KrakenClient) -> Response {
+    loop {
+        match client.read().await.expect("read message") {
+            KrakenMessage::Snapshot(response) | KrakenMessage::Update(response) => {
+                return response;
+            }
+            _ => continue,
+        }
+    }
+}
+
+#[tokio::test]
+async fn subscribes_and_streams_fixture() {
+    let script = Script::from_fixture(FIXTURE).unwrap();
+    let mock = MockKraken::start(MockConfig::default().connection(script))
+        .await
+        .unwrap();
+    let mut client = connect(&mock).await;
+
+    let mut received = Vec::new();
+    for _ in 0..4 {
+        received.push(next_response(&mut client).await.message_type);
+    }
+    assert_eq!(received, ["snapshot", "update", "update", "update"]);
+
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 1);
+    let params = &requests[0].params;
+    assert_eq!(requests[0].method, "subscribe");
+    assert_eq!(params.channel, "level3");
+    assert_eq!(params.symbol, ["ETH/BTC"]);
+    assert_eq!(params.depth, Some(1000));
+    assert_eq!(params.token, "mock-token-1");
+    assert_eq!(params.req_id, Some(1));
+}
+
+#[tokio::test]
+async fn mirrors_fixture_at_instrument_precision() {
+    let script = Script::from_fixture(FIXTURE).unwrap();
+    let mock = MockKraken::start(MockConfig::default().connection(script))
+        .await
+        .unwrap();
+    let mut client = connect(&mock).await;
+    let mut books = BookSet::with_symbols(["ETH/BTC"]);
+
+    for _ in 0..4 {
+        let response = next_response(&mut client).await;
+        process_message(&mut books, response).expect("apply fixture");
+    }
+
+    let book = books.book("ETH/BTC").unwrap();
+    let bids: Vec<u64> = book.orders.bids().map(|(price, _)| price).collect();
+    assert_eq!(bids, [3011, 3010]);
+    let asks: Vec<u64> = book.orders.asks().map(|(price, _)| price).collect();
+    assert_eq!(asks, [3012, 3013]);
+
+    let modified = book.orders.get("OASK2-AAAAA-000004").unwrap();
+    assert_eq!(modified.side, BookSide::Ask);
+    assert_eq!(book.spec.format_price(modified.price), "0.03013");
+    assert_eq!(book.spec.format_qty(modified.qty), "3.00000000");
+    assert!(book.orders.get("OBID2-AAAAA-000002").is_none());
+}
+
+#[tokio::test]
+async fn reconnects_after_dropped_connection() {
+    let snapshot = std::fs::read_to_string(FIXTURE).unwrap();
+    let first = snapshot.lines().next().unwrap().to_string();
+    // A heartbeat well before the snapshot on the new connection
+    let mut second = Script::new()
+        .step(Step::Send(r#"{"channel":"heartbeat"}"#.to_string()))
+        .step(Step::Delay(Duration::from_millis(100)));
+    second
+        .steps
+        .extend(Script::from_fixture(FIXTURE).unwrap().steps);
+    let config = MockConfig::default()
+        .connection(Script::new().step(Step::Send(first)).step(Step::Drop))
+        .connection(second);
+    let mock = MockKraken::start(config).await.unwrap();
+    let mut client = connect(&mock).await;
+
+    let mut resynced = false;
+    let mut snapshots = 0;
+    while snapshots < 2 {
+        match client.next_event().await.expect("supervised read") {
+            FeedEvent::Resync => resynced = true,
+            FeedEvent::Message(KrakenMessage::Snapshot(_)) => snapshots += 1,
+            FeedEvent::Message(_) => {}
+        }
+    }
+
+    assert!(resynced);
+    assert_eq!(client.connection_stats().reconnects, 1);
+    assert!(client.connection_stats().stale >= Duration::from_millis(100));
+    assert_eq!(mock.connections(), 2);
+    assert_eq!(mock.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn records_frames_per_connection() {
+    let snapshot = std::fs::read_to_string(FIXTURE).unwrap();
+    let first = snapshot.lines().next().unwrap().to_string();
+    let config = MockConfig::default()
+        .connection(
+            Script::new()
+                .step(Step::Send(first.clone()))
+                .step(Step::Drop),
+        )
+        .connection(Script::new().step(Step::Send(first.clone())));
+    let mock = MockKraken::start(config).await.unwrap();
+    let path = std::env::temp_dir().join(format!("matchstick-mock-{}.capture", std::process::id()));
+    let mut client = builder(&mock).record_to(&path).connect().await.unwrap();
+
+    let mut snapshots = 0;
+    while snapshots < 2 {
+        if let FeedEvent::Message(KrakenMessage::Snapshot(_)) = client.next_event().await.unwrap() {
+            snapshots += 1;
+        }
+    }
+    assert_eq!(client.connection_id(), 2);
+    client.recorder_mut().unwrap().flush().unwrap();
+
+    let reader = CaptureReader::open(&path).unwrap();
+    assert_eq!(reader.header().subscription.symbols, ["ETH/BTC"]);
+    let records: Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+    let snapshots: Vec<u32> = records
+        .iter()
+        .filter(|record| record.kind == RecordKind::Frame && record.text() == Some(first.as_str()))
+        .map(|record| record.connection)
+        .collect();
+    assert_eq!(snapshots, [1, 2]);
+    let disconnects = records
+        .iter()
+        .filter(|record| record.kind == RecordKind::Disconnect)
+        .map(|record| record.connection);
+    assert!(disconnects.eq([1]));
+    let subscriptions = records
+        .iter()
+        .filter(|record| record.kind == RecordKind::Subscription)
+        .map(|record| record.connection);
+    assert!(subscriptions.eq([1, 2]));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn passes_through_malformed_and_delayed_frames() {
+    let fixture = std::fs::read_to_string(FIXTURE).unwrap();
+    let script = Script::new()
+        .step(Step::Delay(Duration::from_millis(20)))
+        .step(Step::SendMalformed)
+        .step(Step::Send(fixture.lines().next().unwrap().to_string()));
+    let mock = MockKraken::start(MockConfig::default().connection(script))
+        .await
+        .unwrap();
+    let mut client = connect(&mock).await;
+
+    let malformed = client.read().await;
+    assert!(matches!(malformed, Err(ConnectionError::Malformed(_))));
+
+    let snapshot = client.read().await.unwrap();
+    assert!(matches!(snapshot, KrakenMessage::Snapshot(_)));
+}
+
+#[tokio::test]
+async fn answers_pings_and_reports_close() {
+    let script = Script::new()
+        .step(Step::Ping)
+        .step(Step::Send(r#"{"channel":"heartbeat"}"#.to_string()))
+        .step(Step::Close);
+    let mock = MockKraken::start(MockConfig::default().connection(script))
+        .await
+        .unwrap();
+    let mut client = connect(&mock).await;
+
+    let messages: Vec<_> = client.by_ref().take(2).collect().await;
+    assert!(matches!(messages[0], Ok(KrakenMessage::Heartbeat)));
+    assert!(matches!(messages[1], Ok(KrakenMessage::Closed { .. })));
+}
+
+#[tokio::test]
+async fn rejects_subscription_with_error_ack() {
+    let script = Script::new().reject("EGeneral:Invalid arguments:symbol");
+    let mock = MockKraken::start(MockConfig::default().connection(script))
+        .await
+        .unwrap();
+
+    match builder(&mock).connect().await {
+        Err(ConnectionError::Rejected {
+            method,
+            req_id,
+            symbol,
+            reason,
+        }) => {
+            assert_eq!(method, "subscribe");
+            assert_eq!(req_id, 1);
+            assert_eq!(symbol.as_deref(), Some("ETH/BTC"));
+            assert_eq!(reason, RejectReason::UnknownSymbol);
+        }
+        Err(e) => panic!("expected a rejection, got {}", e),
+        Ok(_) => panic!("expected a rejection, got a connection"),
+    }
+}
+
+#[tokio::test]
+async fn changes_subscriptions_at_runtime() {
+    let mock = MockKraken::start(MockConfig::default()).await.unwrap();
+    let mut client = connect(&mock).await;
+
+    client.subscribe(["BTC/USD", "ETH/USD"]).await.unwrap();
+    assert_eq!(
+        client.subscription().symbols,
+        ["ETH/BTC", "BTC/USD", "ETH/USD"]
+    );
+
+    client.unsubscribe(["ETH/BTC"]).await.unwrap();
+    assert_eq!(client.subscription().symbols, ["BTC/USD", "ETH/USD"]);
+
+    let requests = mock.requests();
+    let summary: Vec<_> = requests
+        .iter()
+        .map(|request| (request.method.as_str(), request.params.req_id))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("subscribe", Some(1)),
+            ("subscribe", Some(2)),
+            ("unsubscribe", Some(3))
+        ]
+    );
+    assert_eq!(mock.connections(), 1);
+}
+
+#[tokio::test]
+async fn reports_structured_subscribe_failures() {
+    let mock = MockKraken::start(MockConfig::default()).await.unwrap();
+    let mut client = connect(&mock).await;
+
+    let err = client.subscribe(["BTC/USD", "DOGE/XYZ"]).await.unwrap_err();
+    assert!(matches!(
+        err,
+        ConnectionError::Rejected {
+            reason: RejectReason::UnknownSymbol,
+            ref symbol,
+            ..
+        } if symbol.as_deref() == Some("DOGE/XYZ")
+    ));
+    // The pair Kraken accepted stays subscribed
+    assert_eq!(client.subscription().symbols, ["ETH/BTC", "BTC/USD"]);
+
+    let err = client.unsubscribe(["ETH/USD"]).await.unwrap_err();
+    assert!(matches!(err, ConnectionError::InvalidSubscription(_)));
+}
+
+#[tokio::test]
+async fn resubscribes_after_a_checksum_mismatch() {
+    let snapshot = r#"{"channel":"level3","type":"snapshot","data":[{"symbol":"ETH/BTC","checksum":1,"bids":[{"order_id":"OBID1","limit_price":0.03010,"order_qty":1.5,"timestamp":"2026-10-18T12:00:00Z"}],"asks":[]}]}"#;
+    let script = Script::new().step(Step::Send(snapshot.to_string()));
+    let mock = MockKraken::start(MockConfig::default().connection(script))
+        .await
+        .unwrap();
+    let mut client = connect(&mock).await;
+    let mut books = BookSet::with_symbols(["ETH/BTC"]);
+    let mut quality = QualityStats::default();
+
+    let report = process_message(&mut books, next_response(&mut client).await).unwrap();
+    let [mismatch] = report.mismatches.as_slice() else {
+        panic!(
+            "expected one checksum mismatch, got {:?}",
+            report.mismatches
+        );
+    };
+    assert_eq!(
+        (mismatch.symbol.as_str(), mismatch.expected),
+        ("ETH/BTC", 1)
+    );
+    quality.record_report(&report);
+    assert_eq!(quality.checksum_mismatches, 1);
+
+    client.resubscribe_symbol("ETH/BTC").await.unwrap();
+    let methods: Vec<String> = mock
+        .requests()
+        .iter()
+        .map(|request| request.method.clone())
+        .collect();
+    assert_eq!(methods, ["subscribe", "unsubscribe", "subscribe"]);
+    assert!(client.pending_resyncs().is_empty());
+    assert_eq!(client.subscription().symbols, ["ETH/BTC"]);
+}
+
+#[tokio::test]
+async fn retries_a_failed_resubscribe() {
+    let mock = MockKraken::start(MockConfig::default()).await.unwrap();
+    let mut client = connect(&mock).await;
+
+    mock.delist("ETH/BTC");
+    assert!(client.resubscribe_symbol("ETH/BTC").await.is_err());
+    assert_eq!(client.pending_resyncs(), ["ETH/BTC"]);
+    assert!(client.retry_resyncs().await.is_err());
+    assert_eq!(client.pending_resyncs(), ["ETH/BTC"]);
+
+    mock.list("ETH/BTC");
+    client.retry_resyncs().await.unwrap();
+    assert!(client.pending_resyncs().is_empty());
+    assert_eq!(client.subscription().symbols, ["ETH/BTC"]);
+    let methods: Vec<String> = mock
+        .requests()
+        .iter()
+        .rev()
+        .take(2)
+        .map(|request| request.method.clone())
+        .collect();
+    assert_eq!(methods, ["subscribe", "unsubscribe"]);
+}
+
+#[tokio::test]
+async fn times_out_waiting_for_ack() {
+    let mock = MockKraken::start(MockConfig::default()).await.unwrap();
+    // Tokens still come from the mock, the WebSocket never answers
+    let err = builder(&mock)
+        .endpoints(matchstick::client::Endpoints {
+            ws_url: format!("ws://{}", silent_listener().await),
+            ..mock.endpoints()
+        })
+        .ack_timeout(Duration::from_millis(50))
+        .connect()
+        .await;
+    assert!(matches!(
+        err,
+        Err(ConnectionError::AckTimeout { req_id: 1 })
+    ));
+}
+
+fn token_manager(mock: &MockKraken) -> TokenManager {
+    TokenManager::new(MockKraken::credentials(), &mock.endpoints().token_url).unwrap()
+}
+
+#[tokio::test]
+async fn reads_without_refreshing_tokens() {
+    // Every token is inside the refresh margin as soon as it arrives
+    let config = MockConfig {
+        token_expires: 60,
+        ..MockConfig::default()
+    }
+    .connection(Script::from_fixture(FIXTURE).unwrap());
+    let mock = MockKraken::start(config).await.unwrap();
+    let mut client = connect(&mock).await;
+
+    let mut responses = 0;
+    while responses < 4 {
+        if let FeedEvent::Message(KrakenMessage::Snapshot(_) | KrakenMessage::Update(_)) =
+            client.next_event().await.expect("supervised read")
+        {
+            responses += 1;
+        }
+    }
+    assert_eq!(mock.token_requests(), 1);
+}
+
+#[tokio::test]
+async fn reuses_a_valid_cached_token() {
+    let mock = MockKraken::start(MockConfig::default()).await.unwrap();
+    let mut tokens = token_manager(&mock);
+
+    let first = tokens.token().await.unwrap();
+    let second = tokens.token().await.unwrap();
+    assert_eq!(first.value, second.value);
+    assert_eq!(mock.token_requests(), 1);
+
+    tokens.invalidate();
+    assert_eq!(tokens.token().await.unwrap().value, "mock-token-2");
+}
+
+#[tokio::test]
+async fn refreshes_tokens_inside_the_margin() {
+    // Inside the default 60s margin as soon as it is issued
+    let mock = MockKraken::start(MockConfig {
+        token_expires: 60,
+        ..MockConfig::default()
+    })
+    .await
+    .unwrap();
+    let mut tokens = token_manager(&mock);
+    assert!(tokens.needs_refresh());
+    tokens.token().await.unwrap();
+    assert!(tokens.needs_refresh());
+    assert_eq!(tokens.token().await.unwrap().value, "mock-token-2");
+
+    // Outside a narrower margin, so kept
+    let mut tokens = token_manager(&mock).with_refresh_margin(Duration::from_secs(30));
+    let token = tokens.token().await.unwrap();
+    assert!(!tokens.needs_refresh());
+    assert_eq!(tokens.token().await.unwrap().value, token.value);
+    assert_eq!(mock.token_requests(), 3);
+}
+
+#[tokio::test]
+async fn rejects_a_token_that_expired_on_arrival() {
+    let mock = MockKraken::start(MockConfig {
+        token_expires: 0,
+        ..MockConfig::default()
+    })
+    .await
+    .unwrap();
+    let mut tokens = token_manager(&mock);
+    assert!(matches!(
+        tokens.token().await,
+        Err(ConnectionError::TokenExpired)
+    ));
+    assert_eq!(mock.token_requests(), 1);
+}
+
+#[tokio::test]
+async fn rejects_a_malformed_api_secret() {
+    let mock = MockKraken::start(MockConfig::default()).await.unwrap();
+    let credentials = Credentials::new(MOCK_API_KEY, "not base64!");
+    let mut tokens = TokenManager::new(credentials.clone(), &mock.endpoints().token_url).unwrap();
+    assert!(matches!(
+        tokens.token().await,
+        Err(ConnectionError::InvalidApiSecret)
+    ));
+
+    let err = builder(&mock).credentials(credentials).connect().await;
+    assert!(matches!(err, Err(ConnectionError::InvalidApiSecret)));
+    assert_eq!(mock.token_requests(), 0);
+}
+
+/// A WebSocket server that accepts connections and never replies.
+async fn silent_listener() -> std::net::SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((socket, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+                while ws.next().await.is_some() {}
+            });
+        }
+    });
+    addr
+}
@@ -0,0 +1,204 @@
+use chrono::DateTime;
+use matchstick::recorder::{
+    CaptureError, CaptureHeader, CaptureInfo, CaptureReader, RecordKind, Recorder,
+    SubscriptionRecord,
+};
+use matchstick::subscription::SubscriptionConfig;
+use std::fs::OpenOptions;
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn capture_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "matchstick-{}-{}.capture",
+        name,
+        std::process::id()
+    ))
+}
+
+fn subscription() -> SubscriptionConfig {
+    SubscriptionConfig {
+        symbols: vec!["ETH/BTC".to_string(), "BTC/USD".to_string()],
+        depth: 100,
+        ..SubscriptionConfig::default()
+    }
+}
+
+#[test]
+fn round_trips_header_and_records() {
+    let path = capture_path("round-trip");
+    let header = CaptureHeader::kraken(&subscription());
+    let mut recorder = Recorder::create(&path, header.clone()).unwrap();
+    recorder.record_subscription(1, &subscription()).unwrap();
+    recorder
+        .record_frame(1, r#"{"channel":"heartbeat"}"#)
+        .unwrap();
+    recorder.record_disconnect(1).unwrap();
+    recorder.record_frame(2, r#"{"channel":"status"}"#).unwrap();
+    recorder.flush().unwrap();
+    assert_eq!(recorder.records(), 4);
+
+    let reader = CaptureReader::open(&path).unwrap();
+    assert_eq!(reader.header(), &header);
+    let eth = reader.header().instrument("ETH/BTC").unwrap();
+    assert_eq!((eth.price_precision, eth.qty_precision), (5, 8));
+    assert_eq!(reader.header().subscription.depth, 100);
+
+    let records: Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+    let summary: Vec<_> = records
+        .iter()
+        .map(|record| (record.kind, record.connection, record.text().unwrap()))
+        .collect();
+    assert_eq!(
+        summary[1..],
+        [
+            (RecordKind::Frame, 1, r#"{"channel":"heartbeat"}"#),
+            (RecordKind::Disconnect, 1, ""),
+            (RecordKind::Frame, 2, r#"{"channel":"status"}"#),
+        ]
+    );
+    let subscribed: SubscriptionRecord = serde_json::from_slice(&records[0].payload).unwrap();
+    assert_eq!(subscribed.subscription, subscription());
+    assert_eq!(subscribed.instruments, header.instruments);
+    assert!(
+        records
+            .windows(2)
+            .all(|pair| pair[0].mono_ns <= pair[1].mono_ns)
+    );
+    assert!(records[0].wall_time() >= header.started_at);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn subscription_records_carry_instrument_specs() {
+    let resubscribed = SubscriptionConfig {
+        symbols: vec!["ETH/USD".to_string(), "SOL/USD".to_string()],
+        ..subscription()
+    };
+    let record = SubscriptionRecord::kraken(&resubscribed);
+    let eth = record.instrument("ETH/USD").unwrap();
+    assert_eq!((eth.price_precision, eth.qty_precision), (2, 8));
+    assert_eq!(record.instrument("SOL/USD").unwrap().price_precision, 8);
+
+    // Records written before instruments were added still read
+    let json = serde_json::to_vec(&resubscribed).unwrap();
+    let old: SubscriptionRecord = serde_json::from_slice(&json).unwrap();
+    assert_eq!(old.subscription, resubscribed);
+    assert!(old.instruments.is_empty());
+}
+
+#[test]
+fn appends_after_a_truncated_record() {
+    let path = capture_path("append");
+    let mut recorder = Recorder::create(&path, CaptureHeader::kraken(&subscription())).unwrap();
+    recorder.record_frame(1, "first").unwrap();
+    recorder.flush().unwrap();
+    recorder.record_frame(1, "cut short").unwrap();
+    recorder.flush().unwrap();
+    // Crash before the index is written, and partway through the last block
+    std::mem::forget(recorder);
+
+    let file = OpenOptions::new().write(true).open(&path).unwrap();
+    let len = file.metadata().unwrap().len();
+    file.set_len(len - 3).unwrap();
+    let mut reader = CaptureReader::open(&path).unwrap();
+    assert!(reader.next_record().unwrap().is_some());
+    assert!(matches!(
+        reader.next_record(),
+        Err(CaptureError::Truncated { .. })
+    ));
+
+    let mut recorder = Recorder::append(&path).unwrap();
+    assert_eq!(recorder.records(), 1);
+    recorder.record_frame(2, "second").unwrap();
+    recorder.finish().unwrap();
+
+    let reader = CaptureReader::open(&path).unwrap();
+    assert_eq!(reader.index().map(<[_]>::len), Some(2));
+    let payloads: Vec<_> = reader
+        .map(|record| String::from_utf8(record.unwrap().payload).unwrap())
+        .collect();
+    assert_eq!(payloads, ["first", "second"]);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn rejects_files_that_are_not_captures() {
+    let path = capture_path("not-a-capture");
+    std::fs::write(&path, "{\"channel\":\"level3\"}\n").unwrap();
+    assert!(matches!(
+        CaptureReader::open(&path),
+        Err(CaptureError::NotACapture)
+    ));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn seeks_to_a_time_through_the_block_index() {
+    let path = capture_path("seek");
+    let mut recorder = Recorder::create(&path, CaptureHeader::kraken(&subscription())).unwrap();
+    for frame in ["a", "b", "c"] {
+        recorder.record_frame(1, frame).unwrap();
+        // One block per frame, each at a distinct time
+        recorder.flush().unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    recorder.finish().unwrap();
+
+    let mut reader = CaptureReader::open(&path).unwrap();
+    let index = reader.index().unwrap().to_vec();
+    assert_eq!(index.len(), 3);
+    assert!(index.iter().all(|block| block.records == 1));
+
+    let second = DateTime::from_timestamp_nanos(index[1].first_wall_ns);
+    reader.seek_to(second).unwrap();
+    let payloads: Vec<_> = reader
+        .by_ref()
+        .map(|record| record.unwrap().payload)
+        .collect();
+    assert_eq!(payloads, [b"b".to_vec(), b"c".to_vec()]);
+
+    let after = DateTime::from_timestamp_nanos(index[2].last_wall_ns + 1);
+    reader.seek_to(after).unwrap();
+    assert!(reader.next_record().unwrap().is_none());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn summarizes_capture_contents() {
+    let path = capture_path("info");
+    let mut recorder = Recorder::create(&path, CaptureHeader::kraken(&subscription())).unwrap();
+    let update = r#"{"channel":"level3","type":"update","data":[]}"#;
+    recorder.record_frame(1, update).unwrap();
+    recorder.record_frame(1, update).unwrap();
+    recorder
+        .record_frame(1, r#"{"method":"subscribe","success":true}"#)
+        .unwrap();
+    recorder.record_disconnect(1).unwrap();
+    let resubscribed = SubscriptionConfig {
+        symbols: vec!["ETH/USD".to_string()],
+        ..subscription()
+    };
+    recorder.record_subscription(2, &resubscribed).unwrap();
+    recorder.finish().unwrap();
+
+    let info = CaptureInfo::read(&path).unwrap();
+    assert_eq!(info.version, 3);
+    assert_eq!(info.venue, "kraken");
+    assert_eq!(info.records, 5);
+    assert_eq!(info.disconnects, 1);
+    assert_eq!(info.blocks, Some(1));
+    assert!(!info.truncated);
+    assert_eq!(
+        info.symbols.iter().map(String::as_str).collect::<Vec<_>>(),
+        ["BTC/USD", "ETH/BTC", "ETH/USD"]
+    );
+    assert_eq!(info.messages["level3 update"], 2);
+    assert_eq!(info.messages["subscribe"], 1);
+    assert!(info.first <= info.last);
+
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,61 @@
+use matchstick::client::ConnectionError;
+use matchstick::subscription::SubscriptionConfig;
+
+fn config(symbols: &[&str]) -> SubscriptionConfig {
+    SubscriptionConfig {
+        symbols: symbols.iter().map(|symbol| symbol.to_string()).collect(),
+        ..SubscriptionConfig::default()
+    }
+}
+
+fn rejection(config: &SubscriptionConfig) -> String {
+    match config.validate() {
+        Err(ConnectionError::InvalidSubscription(reason)) => reason,
+        other => panic!("expected InvalidSubscription, got {:?}", other),
+    }
+}
+
+#[test]
+fn accepts_a_valid_config() {
+    let config = SubscriptionConfig {
+        depth: 10,
+        ..config(&["BTC/USD", "ETH/BTC"])
+    };
+    assert!(config.validate().is_ok());
+    assert!(SubscriptionConfig::default().validate().is_ok());
+}
+
+#[test]
+fn rejects_unsupported_depths() {
+    for depth in [0, 25, 500, 10_000] {
+        let config = SubscriptionConfig {
+            depth,
+            ..config(&["BTC/USD"])
+        };
+        assert!(rejection(&config).contains(&depth.to_string()));
+    }
+}
+
+#[test]
+fn rejects_duplicate_symbols() {
+    let reason = rejection(&config(&["BTC/USD", "ETH/BTC", "BTC/USD"]));
+    assert!(reason.contains("duplicate"));
+}
+
+#[test]
+fn rejects_symbols_not_of_the_form_base_quote() {
+    for symbol in ["BTCUSD", "/USD", "BTC/", "BTC/USD/EUR", ""] {
+        let reason = rejection(&config(&[symbol]));
+        assert!(reason.contains("BASE/QUOTE"), "{}: {}", symbol, reason);
+    }
+    assert!(rejection(&config(&[])).contains("no symbols"));
+}
+
+#[test]
+fn rejects_channels_other_than_level3() {
+    let config = SubscriptionConfig {
+        channel: "book".to_string(),
+        ..config(&["BTC/USD"])
+    };
+    assert!(rejection(&config).contains("unsupported channel"));
+}